@@ -1,9 +1,40 @@
-use std::{fs, path::Path};
+//! AppStream metainfo for a single component.
+//!
+//! [`AppStream`] wraps an [`AppStreamComponent`], which is built from the
+//! other types here and written with [`AppStream::to_xml`] or
+//! [`AppStream::write`]. Empty optional parts are left out of the XML.
+//! [`Shipped`] reads back the name, summary, description and categories of an
+//! existing metainfo file, including ones written here.
 
-use serde::Serialize;
+use std::{collections::BTreeMap, fs, path::{Path, PathBuf}, process::Command, str::FromStr};
+
+use serde::{Deserialize, Serialize, Serializer};
+use thiserror::Error;
 
 use crate::licensing::License;
 
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("The AppStream metadata is not valid:\n{0}")]
+    Invalid(String),
+
+    #[error("Couldn't run appstreamcli")]
+    CouldntValidate(#[source] std::io::Error),
+
+    #[error("Couldn't write the AppStream metadata")]
+    CouldntWrite(#[from] std::io::Error),
+
+    #[error("Couldn't serialize the AppStream metadata: {0}")]
+    CouldntSerialize(#[from] quick_xml::SeError),
+
+    #[error("Couldn't read {}: {source}", path.display())]
+    CouldntRead { path: PathBuf, source: std::io::Error },
+
+    #[error("Couldn't parse the AppStream metadata: {0}")]
+    CouldntParse(#[from] quick_xml::DeError),
+}
+
+/// A metainfo file, which holds one component
 pub struct AppStream {
     pub component: AppStreamComponent,
 }
@@ -17,8 +48,8 @@ pub struct AppStreamComponent {
     pub id: String,
     pub metadata_license: License,
     pub project_license:  License,
-    pub name: String,
-    pub summary: String,
+    pub name: LocalizedString,
+    pub summary: LocalizedString,
     pub description: Description,
     pub launchable: Launchable,
     pub content_rating: ContentRating,
@@ -26,10 +57,97 @@ pub struct AppStreamComponent {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub url: Option<Url>,
 
+    #[serde(skip_serializing_if = "Keywords::is_empty")]
+    pub keywords: Keywords,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project_group: Option<String>,
+
+    /// Written with '@' replaced by `_at_`, as AppStream expects
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(serialize_with = "serialize_email")]
+    pub update_contact: Option<String>,
+
     #[serde(skip_serializing_if = "Screenshots::is_empty")]
     pub screenshots: Screenshots,
 
     pub provides: Provides,
+
+    #[serde(skip_serializing_if = "SupportsControl::is_empty")]
+    pub recommends: SupportsControl,
+
+    #[serde(skip_serializing_if = "Releases::is_empty")]
+    pub releases: Releases,
+
+    #[serde(skip_serializing_if = "Custom::is_empty")]
+    pub custom: Custom,
+}
+
+fn serialize_email<S>(email: &Option<String>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match email {
+        Some(email) => serializer.serialize_str(&email.replace('@', "_at_")),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// A text with an untagged default value and optional translations, written
+/// as sibling elements carrying an `xml:lang` attribute.
+#[derive(Default)]
+pub struct LocalizedString {
+    pub default: String,
+
+    // A BTreeMap keeps the output ordered by language
+    pub translations: BTreeMap<String, String>,
+}
+
+impl LocalizedString {
+    pub fn new(default: String) -> Self {
+        Self {
+            default,
+            translations: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_translations<I>(mut self, translations: I) -> Self
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        self.translations.extend(translations);
+        self
+    }
+}
+
+#[derive(Serialize)]
+struct LocalizedText<'a> {
+    #[serde(rename = "@xml:lang")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lang: Option<&'a str>,
+
+    #[serde(rename = "$text")]
+    text: &'a str,
+}
+
+impl Serialize for LocalizedString {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        // A sequence is written by quick-xml as repeated elements with the
+        // field's name, which is exactly what AppStream expects
+        let default = LocalizedText { lang: None, text: &self.default };
+        let translated = self
+            .translations
+            .iter()
+            .map(|(lang, text)| LocalizedText {
+                lang: Some(lang.as_str()),
+                text: text.as_str(),
+            });
+
+        serializer.collect_seq(std::iter::once(default).chain(translated))
+    }
 }
 
 #[derive(Serialize)]
@@ -43,11 +161,217 @@ impl Screenshots {
     }
 }
 
+#[derive(Default, Serialize)]
+pub struct Keywords {
+    pub keyword: Vec<String>,
+}
+
+impl Keywords {
+    pub fn is_empty(&self) -> bool {
+        self.keyword.is_empty()
+    }
+}
+
+/// Versions of the app, newest first
+#[derive(Default, Serialize)]
+pub struct Releases {
+    pub release: Vec<Release>,
+}
+
+impl Releases {
+    /// Just the version being packaged, if it's known
+    pub fn current(version: Option<&str>) -> Self {
+        Self { release: version.map(|v| Release { version: v.to_string() }).into_iter().collect() }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.release.is_empty()
+    }
+}
+
+#[derive(Serialize)]
+pub struct Release {
+    #[serde(rename = "@version")]
+    pub version: String,
+}
+
+/// Input methods the app works well with, written as `<control>` elements
+/// inside `<recommends>`
+#[derive(Default, Serialize)]
+pub struct SupportsControl {
+    pub control: Vec<Control>,
+}
+
+impl SupportsControl {
+    pub fn is_empty(&self) -> bool {
+        self.control.is_empty()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Control {
+    Pointing,
+    Keyboard,
+    Touch,
+    Gamepad,
+}
+
+impl Control {
+    pub const NAMES: [&'static str; 4] = ["pointing", "keyboard", "touch", "gamepad"];
+}
+
+impl FromStr for Control {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pointing" => Ok(Control::Pointing),
+            "keyboard" => Ok(Control::Keyboard),
+            "touch" => Ok(Control::Touch),
+            "gamepad" => Ok(Control::Gamepad),
+            _ => Err(format!("'{s}' is not a control, use one of: {}", Self::NAMES.join(", "))),
+        }
+    }
+}
+
+/// Free-form key-value pairs, written inside `<custom>`
+#[derive(Default, Serialize)]
+pub struct Custom {
+    pub value: Vec<CustomValue>,
+}
+
+impl Custom {
+    pub fn is_empty(&self) -> bool {
+        self.value.is_empty()
+    }
+}
+
 #[derive(Serialize)]
+pub struct CustomValue {
+    #[serde(rename = "@key")]
+    pub key: String,
+
+    #[serde(rename = "$text")]
+    pub value: String,
+}
+
+#[derive(Deserialize, Serialize)]
 pub struct Description {
-    pub p: String
+    #[serde(rename = "$value")]
+    pub blocks: Vec<DescriptionBlock>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub enum DescriptionBlock {
+    #[serde(rename = "p")]
+    Paragraph(String),
+
+    #[serde(rename = "ul")]
+    List(List),
 }
 
+#[derive(Deserialize, Serialize)]
+pub struct List {
+    pub li: Vec<String>,
+}
+
+impl Description {
+    /// Reads a markdown-ish text, paragraphs are separated by blank lines and
+    /// lines starting with `- ` are list items
+    pub fn parse(text: &str) -> Self {
+        fn flush(blocks: &mut Vec<DescriptionBlock>, paragraph: &mut Vec<&str>, items: &mut Vec<String>) {
+            if !paragraph.is_empty() {
+                blocks.push(DescriptionBlock::Paragraph(paragraph.join(" ")));
+                paragraph.clear();
+            }
+            if !items.is_empty() {
+                blocks.push(DescriptionBlock::List(List { li: std::mem::take(items) }));
+            }
+        }
+
+        let mut blocks = Vec::new();
+        let mut paragraph = Vec::new();
+        let mut items = Vec::new();
+        for line in text.lines().map(str::trim) {
+            if let Some(item) = line.strip_prefix("- ") {
+                if !paragraph.is_empty() {
+                    flush(&mut blocks, &mut paragraph, &mut Vec::new());
+                }
+                items.push(item.trim().to_string());
+            } else if line.is_empty() {
+                flush(&mut blocks, &mut paragraph, &mut items);
+            } else {
+                if !items.is_empty() {
+                    flush(&mut blocks, &mut Vec::new(), &mut items);
+                }
+                paragraph.push(line);
+            }
+        }
+        flush(&mut blocks, &mut paragraph, &mut items);
+
+        Self { blocks }
+    }
+}
+
+/// The parts of a metainfo file the app already ships that are worth keeping
+/// when it's regenerated
+#[derive(Default, Deserialize)]
+pub struct Shipped {
+    #[serde(default)]
+    name: Vec<ShippedText>,
+    #[serde(default)]
+    summary: Vec<ShippedText>,
+    pub description: Option<Description>,
+    #[serde(default)]
+    categories: ShippedCategories,
+}
+
+#[derive(Default, Deserialize)]
+struct ShippedText {
+    // The deserializer drops the `xml:` prefix
+    #[serde(rename = "@lang")]
+    lang: Option<String>,
+
+    #[serde(rename = "$text", default)]
+    text: String,
+}
+
+#[derive(Default, Deserialize)]
+struct ShippedCategories {
+    #[serde(default)]
+    category: Vec<String>,
+}
+
+impl Shipped {
+    pub fn parse(xml: &str) -> Result<Self, Error> {
+        Ok(quick_xml::de::from_str(xml)?)
+    }
+
+    pub fn read(file: &Path) -> Result<Self, Error> {
+        let xml = fs::read_to_string(file).map_err(|source| Error::CouldntRead { path: file.to_path_buf(), source })?;
+        Self::parse(&xml)
+    }
+
+    pub fn name(&self) -> Option<LocalizedString> {
+        localized(&self.name)
+    }
+
+    pub fn summary(&self) -> Option<LocalizedString> {
+        localized(&self.summary)
+    }
+
+    pub fn categories(&self) -> &[String] {
+        &self.categories.category
+    }
+}
+
+/// Needs an untagged text, the tagged ones are its translations
+fn localized(texts: &[ShippedText]) -> Option<LocalizedString> {
+    let default = texts.iter().find(|t| t.lang.is_none()).filter(|t| !t.text.trim().is_empty())?;
+    let translations = texts.iter().filter_map(|t| Some((t.lang.clone()?, t.text.trim().to_string())));
+    Some(LocalizedString::new(default.text.trim().to_string()).with_translations(translations))
+}
 
 #[derive(Serialize)]
 pub enum ComponentType {
@@ -67,6 +391,16 @@ pub struct Launchable {
     pub name: String,
 }
 
+impl Launchable {
+    /// A launchable pointing to the desktop file installed for `id`
+    pub fn desktop(id: &str) -> Self {
+        Self {
+            ctype: LaunchableType::DesktopId,
+            name: desktop_id(id),
+        }
+    }
+}
+
 #[derive(Serialize)]
 pub enum LaunchableType {
     #[serde(rename = "desktop-id")]
@@ -106,33 +440,388 @@ pub enum ScreenshotType {
 #[derive(Serialize)]
 pub struct Provides {
     pub id: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub binary: Option<String>,
+}
+
+impl Provides {
+    pub fn desktop(id: &str) -> Self {
+        Self { id: desktop_id(id), binary: None }
+    }
+
+    /// Console apps are found by the command they provide
+    pub fn console(id: &str, binary: &str) -> Self {
+        Self { id: desktop_id(id), binary: Some(binary.to_string()) }
+    }
+}
+
+/// Checks the basic shape of a BCP-47 tag, a 2-3 letter language optionally
+/// followed by subtags like in `pt-BR` or `zh-Hant`
+pub fn is_language_tag(tag: &str) -> bool {
+    let mut subtags = tag.split('-');
+    let language = subtags
+        .next()
+        .is_some_and(|l| (2..=3).contains(&l.len()) && l.chars().all(|c| c.is_ascii_alphabetic()));
+
+    language && subtags.all(|s| (1..=8).contains(&s.len()) && s.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
+/// The desktop-id for a component, this is the file name the desktop file must
+/// have inside `usr/share/applications`
+pub fn desktop_id(id: &str) -> String {
+    format!("{id}.desktop")
 }
 
 #[derive(Serialize)]
 pub struct ContentRating {
     #[serde(rename="@type")]
-    pub t: String
+    pub t: String,
+
+    pub content_attribute: Vec<ContentAttribute>,
+}
+
+impl ContentRating {
+    pub fn from_profile(profile: RatingProfile) -> Self {
+        Self {
+            t: "oars-1.0".to_string(),
+            content_attribute: profile
+                .attributes()
+                .iter()
+                .map(|(id, value)| ContentAttribute { id: id.to_string(), value: value.to_string() })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ContentAttribute {
+    #[serde(rename = "@id")]
+    pub id: String,
+
+    #[serde(rename = "$text")]
+    pub value: String,
+}
+
+/// Ready-made OARS ratings for those who don't know the attribute ids
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum RatingProfile {
+    /// Nothing objectionable, OARS treats missing attributes as `none`
+    #[default]
+    None,
+
+    /// Cartoon or fantasy violence and mild language
+    Mild,
+
+    /// Moderate violence and language, plus chatting with other users
+    Teen,
+}
+
+impl RatingProfile {
+    pub const NAMES: [&'static str; 3] = ["none", "mild", "teen"];
+
+    pub fn attributes(&self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            RatingProfile::None => &[],
+            RatingProfile::Mild => &[
+                ("violence-cartoon", "mild"),
+                ("violence-fantasy", "mild"),
+                ("language-profanity", "mild"),
+                ("language-humor", "mild"),
+            ],
+            RatingProfile::Teen => &[
+                ("violence-cartoon", "moderate"),
+                ("violence-fantasy", "moderate"),
+                ("violence-realistic", "mild"),
+                ("violence-bloodshed", "mild"),
+                ("language-profanity", "moderate"),
+                ("language-humor", "moderate"),
+                ("social-chat", "moderate"),
+            ],
+        }
+    }
+}
+
+impl FromStr for RatingProfile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(RatingProfile::None),
+            "mild" => Ok(RatingProfile::Mild),
+            "teen" => Ok(RatingProfile::Teen),
+            _ => Err(format!("'{s}' is not a rating profile, use one of: {}", Self::NAMES.join(", "))),
+        }
+    }
 }
 
 impl AppStream {
-    pub fn write(&self, base_path: &Path) {
+    /// Writes the metadata inside `base_path` and returns the path of the file
+    pub fn write(&self, base_path: &Path) -> Result<PathBuf, Error> {
         let appstream_path = base_path.join("usr").join("share").join("metainfo");
         if !appstream_path.exists() {
-            fs::create_dir_all(&appstream_path).unwrap();
+            fs::create_dir_all(&appstream_path)?;
         }
 
-        fs::write(
-            appstream_path.join(format!("{}.appdata.xml", self.component.id)),
-            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>".to_string() + &quick_xml::se::to_string(&self.component).unwrap()
-        )
-        .unwrap();
+        let file_path = appstream_path.join(format!("{}.appdata.xml", self.component.id));
+        fs::write(&file_path, self.to_xml()?)?;
+
+        Ok(file_path)
+    }
+
+    /// The metainfo file, indented and with the XML declaration on its own
+    /// line
+    pub fn to_xml(&self) -> Result<String, Error> {
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        let mut serializer = quick_xml::se::Serializer::new(&mut xml);
+        serializer.indent(' ', 2);
+        self.component.serialize(serializer)?;
+        xml.push('\n');
+        Ok(xml)
+    }
+}
+
+/// Checks a metainfo file with `appstreamcli`, if it's not installed the check
+/// is skipped
+pub fn validate(file: &Path) -> Result<(), Error> {
+    let Ok(appstreamcli) = which::which("appstreamcli") else {
+        return Ok(());
+    };
+
+    let out = Command::new(appstreamcli)
+        .arg("validate")
+        .arg("--no-net")
+        .arg(file)
+        .output()
+        .map_err(Error::CouldntValidate)?;
+
+    if out.status.success() {
+        Ok(())
+    } else {
+        Err(Error::Invalid(
+            String::from_utf8_lossy(&out.stdout).into_owned() + &String::from_utf8_lossy(&out.stderr),
+        ))
     }
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
+    use serde::Serialize;
+
+    use super::{
+        desktop_id, is_language_tag, validate, AppStream, DescriptionBlock, Shipped, AppStreamComponent, ComponentType, ContentRating, Custom, Description, Error, Keywords, Launchable,
+        LocalizedString, Provides, RatingProfile, Releases, Screenshots, SupportsControl,
+    };
+    use crate::licensing::License;
+
     #[test]
     fn a() {
         assert_eq!("a", "a")
     }
+
+    #[derive(Serialize)]
+    #[serde(rename = "component")]
+    struct TestLocalized {
+        name: LocalizedString,
+    }
+
+    #[test]
+    fn localized_name() {
+        let name = LocalizedString::new("Calculator".to_string())
+            .with_translations([("es".to_string(), "Calculadora".to_string())]);
+
+        assert_eq!(
+            quick_xml::se::to_string(&TestLocalized { name }).unwrap(),
+            "<component><name>Calculator</name><name xml:lang=\"es\">Calculadora</name></component>"
+        );
+    }
+
+    #[test]
+    fn invalid_metainfo_is_reported() {
+        if which::which("appstreamcli").is_err() {
+            return;
+        }
+
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("test");
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("invalid.appdata.xml");
+        std::fs::write(&file, "<?xml version=\"1.0\" encoding=\"UTF-8\"?><component></component>").unwrap();
+
+        let res = validate(&file);
+
+        assert!(matches!(res, Err(Error::Invalid(_))));
+    }
+
+    pub(crate) fn calculator(id: &str) -> AppStreamComponent {
+        AppStreamComponent {
+            ctype: ComponentType::DesktopApplication,
+            id: id.to_string(),
+            metadata_license: License::CC0,
+            project_license: License::Mit,
+            name: LocalizedString::new("Calculator".to_string()),
+            summary: LocalizedString::new("Calculates".to_string()),
+            description: Description::parse("Calculates things"),
+            launchable: Launchable::desktop(id),
+            content_rating: ContentRating::from_profile(RatingProfile::None),
+            url: None,
+            keywords: Keywords::default(),
+            project_group: None,
+            update_contact: None,
+            screenshots: Screenshots { screenshot: vec![] },
+            provides: Provides::desktop(id),
+            recommends: SupportsControl::default(),
+            releases: Releases::default(),
+            custom: Custom::default(),
+        }
+    }
+
+    #[test]
+    fn ids_agree() {
+        let id = "com.to_appimage.calculator";
+        let component = calculator(id);
+
+        assert_eq!(desktop_id(&component.id), "com.to_appimage.calculator.desktop");
+        assert_eq!(component.launchable.name, desktop_id(&component.id));
+        assert_eq!(component.provides.id, desktop_id(&component.id));
+
+        let xml = quick_xml::se::to_string(&component).unwrap();
+        assert!(xml.contains("<launchable type=\"desktop-id\">com.to_appimage.calculator.desktop</launchable>"));
+        assert!(xml.contains("<provides><id>com.to_appimage.calculator.desktop</id></provides>"));
+    }
+
+    #[test]
+    fn license_expression() {
+        let mut component = calculator("com.to_appimage.calculator");
+        component.project_license = License::from_id("MIT OR Apache-2.0").unwrap();
+
+        let xml = quick_xml::se::to_string(&component).unwrap();
+        assert!(xml.contains("<project_license>MIT OR Apache-2.0</project_license>"));
+    }
+
+    #[test]
+    fn supported_controls() {
+        let mut component = calculator("com.to_appimage.calculator");
+        let xml = quick_xml::se::to_string(&component).unwrap();
+        assert!(!xml.contains("<recommends>"));
+
+        component.recommends = SupportsControl { control: vec!["keyboard".parse().unwrap(), "touch".parse().unwrap()] };
+        let xml = quick_xml::se::to_string(&component).unwrap();
+        assert!(xml.contains("<recommends><control>keyboard</control><control>touch</control></recommends>"));
+    }
+
+    #[test]
+    fn project_group_and_contact() {
+        let mut component = calculator("com.to_appimage.calculator");
+        let xml = quick_xml::se::to_string(&component).unwrap();
+        assert!(!xml.contains("<project_group>"));
+        assert!(!xml.contains("<update_contact>"));
+
+        component.project_group = Some("GNOME".to_string());
+        component.update_contact = Some("dev@example.org".to_string());
+        let xml = quick_xml::se::to_string(&component).unwrap();
+        assert!(xml.contains("<project_group>GNOME</project_group>"));
+        assert!(xml.contains("<update_contact>dev_at_example.org</update_contact>"));
+    }
+
+    #[test]
+    fn teen_rating() {
+        let mut component = calculator("com.to_appimage.calculator");
+        component.content_rating = ContentRating::from_profile("teen".parse().unwrap());
+
+        let xml = quick_xml::se::to_string(&component).unwrap();
+        assert!(xml.contains(concat!(
+            "<content_rating type=\"oars-1.0\">",
+            "<content_attribute id=\"violence-cartoon\">moderate</content_attribute>",
+            "<content_attribute id=\"violence-fantasy\">moderate</content_attribute>",
+            "<content_attribute id=\"violence-realistic\">mild</content_attribute>",
+            "<content_attribute id=\"violence-bloodshed\">mild</content_attribute>",
+            "<content_attribute id=\"language-profanity\">moderate</content_attribute>",
+            "<content_attribute id=\"language-humor\">moderate</content_attribute>",
+            "<content_attribute id=\"social-chat\">moderate</content_attribute>",
+            "</content_rating>"
+        )));
+    }
+
+    #[test]
+    fn language_tags() {
+        assert!(is_language_tag("es"));
+        assert!(is_language_tag("pt-BR"));
+        assert!(is_language_tag("zh-Hant"));
+        assert!(!is_language_tag("e"));
+        assert!(!is_language_tag("es_ES"));
+        assert!(!is_language_tag("es-"));
+    }
+
+    #[derive(Serialize)]
+    #[serde(rename = "component")]
+    struct TestDescription {
+        description: Description,
+    }
+
+    #[test]
+    fn description_blocks() {
+        let description = Description::parse("First paragraph\nstill the first.\n\nSecond one.\n- One\n- Two\n");
+
+        assert_eq!(
+            quick_xml::se::to_string(&TestDescription { description }).unwrap(),
+            concat!(
+                "<component><description>",
+                "<p>First paragraph still the first.</p>",
+                "<p>Second one.</p>",
+                "<ul><li>One</li><li>Two</li></ul>",
+                "</description></component>"
+            )
+        );
+    }
+
+    #[test]
+    fn indented_xml() {
+        let xml = AppStream { component: calculator("com.to_appimage.calculator") }.to_xml().unwrap();
+
+        assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<component type=\"desktop-application\">\n"));
+        assert!(xml.contains("\n  <id>com.to_appimage.calculator</id>\n"));
+        assert!(xml.contains("  <provides>\n    <id>com.to_appimage.calculator.desktop</id>\n  </provides>\n"));
+
+        let mut reader = quick_xml::Reader::from_str(&xml);
+        loop {
+            match reader.read_event() {
+                Ok(quick_xml::events::Event::Eof) => break,
+                Ok(_) => {}
+                Err(e) => panic!("invalid XML: {e}"),
+            }
+        }
+    }
+
+    #[test]
+    fn shipped_metadata_is_read() {
+        let shipped = Shipped::parse(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<component type="desktop-application">
+  <id>org.example.Notes</id>
+  <name>Notes</name>
+  <name xml:lang="es">Notas</name>
+  <summary>Jot things down</summary>
+  <description>
+    <p>Keeps your notes.</p>
+    <ul>
+      <li>Fast</li>
+    </ul>
+  </description>
+  <categories>
+    <category>Office</category>
+  </categories>
+</component>"#,
+        )
+        .unwrap();
+
+        let name = shipped.name().unwrap();
+        assert_eq!(name.default, "Notes");
+        assert_eq!(name.translations.get("es").map(String::as_str), Some("Notas"));
+        assert_eq!(shipped.summary().unwrap().default, "Jot things down");
+        assert_eq!(shipped.categories(), ["Office"]);
+        let blocks = shipped.description.unwrap().blocks;
+        assert!(matches!(&blocks[0], DescriptionBlock::Paragraph(p) if p == "Keeps your notes."));
+        assert!(matches!(&blocks[1], DescriptionBlock::List(list) if list.li == ["Fast"]));
+    }
 }
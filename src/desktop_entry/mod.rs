@@ -1,3 +1,20 @@
+//! A serde serializer for freedesktop desktop entries.
+//!
+//! The value being serialized has up to three levels:
+//!
+//! 1. A struct or map of groups, written as `[Group Name]` headers
+//! 2. Inside each group, a struct or map of keys, written as `Key=value`
+//! 3. Optionally a map from locale to text, written as `Key[es_ES]=texto`
+//!
+//! Strings, booleans and numbers are written as is, sequences as `a;b;`
+//! lists and `None` or `()` leave the key out. Group names, keys and locales
+//! are checked against the spec and anything deeper than three levels is an
+//! error.
+//!
+//! Values are escaped as the spec asks (`\n`, `\t`, `\r`, `\\` and `\;`
+//! inside lists), so what [`get_value`] reads back and passes through
+//! [`unescape`] or [`split_list`] is what was serialized.
+
 use std::{fmt::Display, io::Write};
 
 use serde::{ser, Serialize};
@@ -7,6 +24,9 @@ use thiserror::Error;
 pub enum Error {
     #[error("{0}")]
     Custom(String),
+
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
 }
 
 impl serde::ser::Error for Error {
@@ -46,6 +66,7 @@ impl LevelTracker {
     fn set_key(&mut self, key: String) {self.key_name = Some(key);}
 }
 
+/// Only used through [`to_string`] and [`to_writer`]
 pub struct Serializer {
     // This string starts empty and JSON is appended as values are serialized.
     output: String,
@@ -53,31 +74,191 @@ pub struct Serializer {
     disable_write_key: bool
 }
 
+/// Serializes `value` as a desktop entry, see the module docs for the shapes
+/// it takes
 pub fn to_string<T>(value:&T) -> Result<String>  where T: ?Sized + Serialize{
     let mut serializer = Serializer{output: String ::new(), level: LevelTracker::new(), disable_write_key: false};
     value.serialize(&mut serializer)?;
     Ok(serializer.output)
 }
 
+/// Like [`to_string`], nothing is written if serializing fails
 pub fn to_writer<W,T>(mut writer: W, value: &T) -> Result<()> 
 where
     W: Write,
     T: ?Sized + Serialize,
 {
-    writer.write_all(to_string(value)?.as_bytes()).unwrap();
+    writer.write_all(to_string(value)?.as_bytes())?;
     Ok(())
 }
 
+/// Undoes the escaping of a value read with [`get_value`], `\;` is left as
+/// is since it only means something in lists
+pub fn unescape(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('s') => unescaped.push(' '),
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some('r') => unescaped.push('\r'),
+            Some('\\') => unescaped.push('\\'),
+            Some(other) => {
+                unescaped.push('\\');
+                unescaped.push(other);
+            }
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// Splits a list read with [`get_value`] into its unescaped items
+pub fn split_list(value: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut item = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(';') => item.push(';'),
+                Some(other) => {
+                    item.push('\\');
+                    item.push(other);
+                }
+                None => item.push('\\'),
+            },
+            ';' => items.push(unescape(&std::mem::take(&mut item))),
+            c => item.push(c),
+        }
+    }
+    if !item.is_empty() {
+        items.push(unescape(&item));
+    }
+    items
+}
+
+// Escapes what can't appear literally in a value, `;` only inside lists
+fn escape(v: &str, in_list: bool) -> String {
+    let mut escaped = String::with_capacity(v.len());
+    for c in v.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            ';' if in_list => escaped.push_str("\\;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Reads the value of `key` inside `group` from a desktop file's contents,
+/// localized variants are ignored
+pub fn get_value<'a>(contents: &'a str, group: &str, key: &str) -> Option<&'a str> {
+    let mut in_group = false;
+    for line in contents.lines().map(str::trim) {
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            in_group = name == group;
+        } else if in_group {
+            if let Some((k, v)) = line.split_once('=') {
+                if k.trim() == key {
+                    return Some(v.trim());
+                }
+            }
+        }
+    }
+
+    None
+}
+
+// Group names can contain any ASCII character but '[', ']' and control ones
+fn validate_group(group: &str) -> Result<()> {
+    if group.is_empty() || group.chars().any(|c| !c.is_ascii() || c.is_ascii_control() || c == '[' || c == ']') {
+        Err(Error::Custom(format!("'{group}' is not a valid group name")))
+    } else {
+        Ok(())
+    }
+}
+
+// Keys can only be made of A-Za-z0-9-
+fn validate_key(key: &str) -> Result<()> {
+    if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        Err(Error::Custom(format!("'{key}' is not a valid key name")))
+    } else {
+        Ok(())
+    }
+}
+
+// Locales look like lang_COUNTRY.ENCODING@MODIFIER
+fn validate_locale(locale: &str) -> Result<()> {
+    if locale.is_empty() || !locale.chars().all(|c| c.is_ascii_alphanumeric() || ['_', '.', '@', '-'].contains(&c)) {
+        Err(Error::Custom(format!("'{locale}' is not a valid locale")))
+    } else {
+        Ok(())
+    }
+}
+
+// Keys whose values are command lines, where '%' starts a field code
+const FIELD_CODE_KEYS: [&str; 1] = ["Exec"];
+
+// Field codes the spec defines, deprecated ones included
+const FIELD_CODES: [char; 13] = ['f', 'F', 'u', 'U', 'i', 'c', 'k', 'd', 'D', 'n', 'N', 'v', 'm'];
+
+// A '%' that doesn't start a field code is doubled, `%%` is already escaped
+fn escape_percent(v: &str) -> String {
+    let mut escaped = String::with_capacity(v.len());
+    let mut chars = v.chars().peekable();
+    while let Some(c) = chars.next() {
+        escaped.push(c);
+        if c == '%' {
+            match chars.peek() {
+                Some(&next) if next == '%' || FIELD_CODES.contains(&next) => {
+                    escaped.push(next);
+                    chars.next();
+                }
+                _ => escaped.push('%'),
+            }
+        }
+    }
+    escaped
+}
+
 impl Serializer {
+    fn in_field_code_key(&self) -> bool {
+        self.level.get_level() == 2 && self.level.get_key().as_deref().is_some_and(|k| FIELD_CODE_KEYS.contains(&k))
+    }
+
     fn write_pre_val(&mut self) {
         if self.level.get_level() == 2 && !self.disable_write_key {
             self.output.push_str(self.level.get_key().as_ref().unwrap());
             self.output.push('=');
         }
     } 
+
+    // Groups are separated by a single blank line, written before the next
+    // header so that the file ends with just one newline
+    fn start_group(&mut self) {
+        if !self.output.is_empty() {
+            self.output.push('\n');
+        }
+    }
+
+    // Each key ends its line, localized keys already did it for every entry
+    fn end_value(&mut self) {
+        if self.level.get_level() > 1 && !self.output.ends_with('\n') {
+            self.output.push('\n');
+        }
+    }
 }
 
-impl<'a> ser::Serializer for &'a mut Serializer {
+impl ser::Serializer for &mut Serializer {
     type Ok = ();
     type Error = Error;
 
@@ -136,9 +317,16 @@ impl<'a> ser::Serializer for &'a mut Serializer {
         self.serialize_f64(f64::from(v))
     }
 
+    // Rust's float formatting never depends on the locale, so the separator is
+    // always '.', but integral values lose their decimal part, which is added
+    // back so that `1.0` doesn't end up as `1`
     fn serialize_f64(self, v: f64) -> Result<()> {
         self.write_pre_val();
-        self.output += &v.to_string();
+        let mut float = v.to_string();
+        if v.is_finite() && !float.contains('.') {
+            float += ".0";
+        }
+        self.output += &float;
         Ok(())
     }
 
@@ -146,12 +334,15 @@ impl<'a> ser::Serializer for &'a mut Serializer {
         self.serialize_str(&v.to_string())
     }
 
-    // This only works for strings that don't require escape sequences but you
-    // get the idea. For example it would emit invalid JSON if the input string
-    // contains a '"' character.
+    // Group names and locales are validated instead, only values are escaped
     fn serialize_str(self, v: &str) -> Result<()> {
         self.write_pre_val();
-        self.output += v;
+        let v = if self.level.get_level() >= 2 { escape(v, self.disable_write_key) } else { v.to_string() };
+        if self.in_field_code_key() {
+            self.output += &escape_percent(&v);
+        } else {
+            self.output += &v;
+        }
         Ok(())
     }
 
@@ -169,7 +360,7 @@ impl<'a> ser::Serializer for &'a mut Serializer {
         seq.end()
     }
 
-    // An absent optional is represented as the JSON `null`.
+    // An absent optional is left out, just like a unit.
     fn serialize_none(self) -> Result<()> {
         self.serialize_unit()
     }
@@ -189,11 +380,9 @@ impl<'a> ser::Serializer for &'a mut Serializer {
         value.serialize(self)
     }
 
-    // In Serde, unit means an anonymous value containing no data. We'll 
-    // translate to an empty string.
+    // In Serde, unit means an anonymous value containing no data. The key
+    // isn't written either, as `Key=` is invalid for many keys.
     fn serialize_unit(self) -> Result<()> {
-        self.write_pre_val();
-        self.output += "";
         Ok(())
     }
 
@@ -338,7 +527,7 @@ impl<'a> ser::Serializer for &'a mut Serializer {
 //
 // This impl is SerializeSeq so these methods are called after `serialize_seq`
 // is called on the Serializer.
-impl<'a> ser::SerializeSeq for &'a mut Serializer {
+impl ser::SerializeSeq for &mut Serializer {
     // Must match the `Ok` type of the serializer.
     type Ok = ();
     // Must match the `Error` type of the serializer.
@@ -364,7 +553,7 @@ impl<'a> ser::SerializeSeq for &'a mut Serializer {
 }
 
 // Same thing but for tuples.
-impl<'a> ser::SerializeTuple for &'a mut Serializer {
+impl ser::SerializeTuple for &mut Serializer {
     type Ok = ();
     type Error = Error;
 
@@ -385,7 +574,7 @@ impl<'a> ser::SerializeTuple for &'a mut Serializer {
 }
 
 // Same thing but for tuple structs.
-impl<'a> ser::SerializeTupleStruct for &'a mut Serializer {
+impl ser::SerializeTupleStruct for &mut Serializer {
     type Ok = ();
     type Error = Error;
 
@@ -414,7 +603,7 @@ impl<'a> ser::SerializeTupleStruct for &'a mut Serializer {
 //
 // So the `end` method in this impl is responsible for closing both the `]` and
 // the `}`.
-impl<'a> ser::SerializeTupleVariant for &'a mut Serializer {
+impl ser::SerializeTupleVariant for &mut Serializer {
     type Ok = ();
     type Error = Error;
 
@@ -442,7 +631,7 @@ impl<'a> ser::SerializeTupleVariant for &'a mut Serializer {
 // `serialize_entry` method allows serializers to optimize for the case where
 // key and value are both available simultaneously. In JSON it doesn't make a
 // difference so the default behavior for `serialize_entry` is fine.
-impl<'a> ser::SerializeMap for &'a mut Serializer {
+impl ser::SerializeMap for &mut Serializer {
     type Ok = ();
     type Error = Error;
 
@@ -463,14 +652,18 @@ impl<'a> ser::SerializeMap for &'a mut Serializer {
         match self.level.get_level() {
             0 => {panic!("EEEErm")}
             1 => {
+                validate_group(&temp_ser.output)?;
+                self.start_group();
                 self.output += "[";
                 key.serialize(&mut **self)?;
                 self.output += "]\n";
             }
             2 => {
+                validate_key(&temp_ser.output)?;
                 self.level.set_key(temp_ser.output);
             }
             3 => {
+                validate_locale(&temp_ser.output)?;
                 self.output += self.level.get_key().as_ref().unwrap();
                 self.output += "[";
                 key.serialize(&mut **self)?;
@@ -489,7 +682,7 @@ impl<'a> ser::SerializeMap for &'a mut Serializer {
         T: ?Sized + Serialize,
     {
         value.serialize(&mut **self)?;
-        self.output+="\n";
+        self.end_value();
         Ok(())
     }
 
@@ -501,7 +694,7 @@ impl<'a> ser::SerializeMap for &'a mut Serializer {
 
 // Structs are like maps in which the keys are constrained to be compile-time
 // constant strings.
-impl<'a> ser::SerializeStruct for &'a mut Serializer {
+impl ser::SerializeStruct for &mut Serializer {
     type Ok = ();
     type Error = Error;
 
@@ -512,14 +705,18 @@ impl<'a> ser::SerializeStruct for &'a mut Serializer {
         match self.level.get_level() {
             0 => {panic!("EEEErm")},
             1 => {
+                validate_group(key)?;
+                self.start_group();
                 self.output += "[";
                 key.serialize(&mut **self)?;
                 self.output += "]\n";
             },
             2 => {
+                validate_key(key)?;
                 self.level.set_key(key.to_string());
             },
             3 => {
+                validate_locale(key)?;
                 self.output += self.level.get_key().as_ref().unwrap();
                 self.output += "[";
                 key.serialize(&mut **self)?;
@@ -528,18 +725,19 @@ impl<'a> ser::SerializeStruct for &'a mut Serializer {
             l => return Err(Error::Custom(format!("freedesktop entries have a maximum of three levels {l}")))
         }
         value.serialize(&mut **self)?;
-        self.output+="\n";
+        self.end_value();
         Ok(())
     }
 
     fn end(self) -> Result<()> {
+        self.level.close_level();
         Ok(())
     }
 }
 
 // Similar to `SerializeTupleVariant`, here the `end` method is responsible for
 // closing both of the curly braces opened by `serialize_struct_variant`.
-impl<'a> ser::SerializeStructVariant for &'a mut Serializer {
+impl ser::SerializeStructVariant for &mut Serializer {
     type Ok = ();
     type Error = Error;
 
@@ -560,9 +758,9 @@ impl<'a> ser::SerializeStructVariant for &'a mut Serializer {
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
+    use std::collections::BTreeMap;
 
-    use super::to_string;
+    use super::{escape_percent, get_value, to_string};
     use serde::Serialize;
     
     #[derive(Serialize)]
@@ -585,7 +783,6 @@ mod tests {
             "[Desktop Entry]
 Test=test string
 c=Another one
-
 "
         );
     }
@@ -598,20 +795,18 @@ c=Another one
 
     #[derive(Serialize)]
     struct InnerTranslations {
-        b: HashMap<String, String>
+        b: BTreeMap<String, String>
     }
 
     #[test]
     fn translations() {
-        let mut map = HashMap::new();
+        let mut map = BTreeMap::new();
         map.insert("es".to_string(), "A".to_uppercase());
         map.insert("en".to_string(), "B".to_string());
         assert_eq!(&to_string(&TestTranslations{a:InnerTranslations{b: map}}).unwrap(),
         "[Desktop Entry]
-b[es]=A
 b[en]=B
-
-
+b[es]=A
 "
     );
     }
@@ -632,7 +827,135 @@ b[en]=B
         assert_eq!(&to_string(&TestSeq{a:InnerSeq{b: vec!["test".to_string(), "string".to_string()]}}).unwrap(),
         "[Desktop Entry]
 b=test;string;
+");
+    }
 
+    #[derive(Serialize)]
+    struct TestExec {
+        #[serde(rename="Desktop Entry")]
+        a: InnerExec
+    }
+
+    #[derive(Serialize)]
+    struct InnerExec {
+        #[serde(rename="Name")]
+        name: String,
+        #[serde(rename="Exec")]
+        exec: String
+    }
+
+    #[test]
+    fn exec_percent_is_doubled() {
+        assert_eq!(&to_string(&TestExec{a:InnerExec{name: "50%".to_string(), exec: "app --zoom 50% %F".to_string()}}).unwrap(),
+        "[Desktop Entry]
+Name=50%
+Exec=app --zoom 50%% %F
+");
+        assert_eq!(escape_percent("100%%"), "100%%");
+        assert_eq!(escape_percent("%"), "%%");
+    }
+
+    #[derive(Serialize)]
+    struct TestBadGroup {
+        #[serde(rename="Desktop]Entry")]
+        a: InnerString
+    }
+
+    #[test]
+    fn invalid_group() {
+        assert!(to_string(&TestBadGroup{a:InnerString { b:"test".to_string(), c:"test".to_string()}}).is_err());
+    }
+
+    #[derive(Serialize)]
+    struct TestBadKey {
+        #[serde(rename="Desktop Entry")]
+        a: InnerBadKey
+    }
+
+    #[derive(Serialize)]
+    struct InnerBadKey {
+        #[serde(rename="Bad=Key")]
+        b: String
+    }
+
+    #[test]
+    fn invalid_key() {
+        assert!(to_string(&TestBadKey{a:InnerBadKey { b:"test".to_string()}}).is_err());
+    }
+
+    #[derive(Serialize)]
+    struct TestGroups {
+        #[serde(rename="Desktop Entry")]
+        a: InnerSeq,
+        #[serde(rename="Desktop Action New")]
+        b: InnerSeq
+    }
+
+    #[test]
+    fn groups() {
+        assert_eq!(&to_string(&TestGroups{
+            a: InnerSeq{b: vec!["a".to_string()]},
+            b: InnerSeq{b: vec!["b".to_string()]}
+        }).unwrap(),
+        "[Desktop Entry]
+b=a;
+
+[Desktop Action New]
+b=b;
+");
+    }
+
+    #[derive(Serialize)]
+    struct TestFloat {
+        #[serde(rename="Desktop Entry")]
+        a: InnerFloat
+    }
+
+    #[derive(Serialize)]
+    struct InnerFloat {
+        #[serde(rename="Version")]
+        version: f64,
+        #[serde(rename="X-Scale")]
+        scale: f32
+    }
+
+    #[test]
+    fn floats() {
+        assert_eq!(&to_string(&TestFloat{a:InnerFloat{version: 1.0, scale: 0.5}}).unwrap(),
+        "[Desktop Entry]
+Version=1.0
+X-Scale=0.5
 ");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn read_value() {
+        let contents = "[Desktop Entry]\nName=App\nIcon[es]=otra\nIcon=myapp\n\n[Desktop Action New]\nIcon=new\n";
+        assert_eq!(get_value(contents, "Desktop Entry", "Icon"), Some("myapp"));
+        assert_eq!(get_value(contents, "Desktop Action New", "Icon"), Some("new"));
+        assert_eq!(get_value(contents, "Desktop Entry", "Exec"), None);
+    }
+
+    #[derive(Serialize)]
+    struct TestOptional {
+        #[serde(rename="Desktop Entry")]
+        a: InnerOptional
+    }
+
+    #[derive(Serialize)]
+    struct InnerOptional {
+        #[serde(rename="Name")]
+        name: String,
+        #[serde(rename="Icon")]
+        icon: Option<String>,
+        #[serde(rename="Comment")]
+        comment: Option<String>,
+    }
+
+    #[test]
+    fn none_is_left_out() {
+        let entry = TestOptional { a: InnerOptional { name: "App".to_string(), icon: None, comment: Some("An app".to_string()) } };
+
+        assert_eq!(to_string(&entry).unwrap(), "[Desktop Entry]\nName=App\nComment=An app\n");
+    }
+}
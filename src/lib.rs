@@ -0,0 +1,5757 @@
+use std::{
+    collections::BTreeMap, ffi::OsString, fs::{self, File}, time::Duration, io::{self, Write}, os::unix::fs::PermissionsExt, path::{Component, Path, PathBuf}, process::Command, str::FromStr
+};
+
+use appstream::{
+    AppStream, AppStreamComponent, ComponentType, ContentRating, Custom, CustomValue, Description, Keywords, Launchable, LocalizedString, Provides, RatingProfile, Releases, Screenshot, ScreenshotType, Screenshots, SupportsControl, Url
+};
+use cmd::{LinkSet, RunExt};
+use image::imageops::resize;
+use itertools::Itertools;
+use licensing::License;
+use rayon::prelude::*;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_yaml::to_writer;
+use thiserror::Error;
+
+
+pub mod appstream;
+pub mod desktop_entry;
+pub mod licensing;
+
+const DEFAULT_ICON: &[u8; 530] = include_bytes!("../default-icon.svg");
+
+const APPIMAGETOOL_LINKSET: LinkSet = LinkSet {
+    x86_64: "https://github.com/AppImage/appimagetool/releases/download/continuous/appimagetool-x86_64.AppImage",
+    arch64: "https://github.com/AppImage/appimagetool/releases/download/continuous/appimagetool-aarch64.AppImage",
+};
+
+/// Everything needed to turn a target into an AppImage, mirrors the CLI
+/// arguments
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub terminal: bool,
+
+    /// Keep the app out of menus, for helpers
+    pub no_display: bool,
+
+    /// Mark the desktop entry as deleted
+    pub hidden: bool,
+
+    /// Name shown to the user, by default it's guessed from the executable
+    pub name: Option<String>,
+
+    /// Secondary label for menus, like "Web Browser"
+    pub generic_name: Option<String>,
+
+    /// Program the menu checks before showing the entry, looked up in PATH
+    /// unless it's an absolute path. Not written if not set, a relative
+    /// `./AppRun` would hide the entry once integrated
+    pub try_exec: Option<String>,
+
+    /// Executable to run, relative to the app's directory, guessed if not set
+    pub exec: Option<String>,
+
+    /// Version of the app, for debs it's read from the package if not set
+    pub app_version: Option<String>,
+
+    /// Extra desktop file keys, they must start with `X-`
+    pub x_keys: Vec<(String, String)>,
+
+    /// Desktop categories, `Utility` if neither these nor a preset give any
+    pub categories: Vec<String>,
+
+    /// Friendly name for a set of categories, like `dev`
+    pub preset: Option<String>,
+
+    /// Search terms, for both the desktop file and the AppStream metadata
+    pub keywords: Vec<String>,
+    pub icon: Option<String>,
+
+    /// Where to download the icon from when there's no local one
+    pub icon_url: Option<String>,
+
+    /// Let strict builds fall back to the placeholder icon
+    pub allow_default_icon: bool,
+
+    /// Image used as the placeholder icon instead of the built-in one
+    pub default_icon: Option<PathBuf>,
+
+    /// Copy the executable's non-system shared libraries into `usr/lib`
+    pub bundle_libs: bool,
+
+    /// Files copied into the AppDir, as a glob and the directory inside the
+    /// AppDir they go to, like GStreamer's plugins
+    pub bundle_extra: Vec<(String, String)>,
+
+    /// Environment variables the AppRun exports before running the app
+    pub env: Vec<(String, String)>,
+
+    /// Point an interpreter and RPATH that only exist on the build host to
+    /// the system loader and the AppDir, needs patchelf
+    pub patch_interpreter: bool,
+
+    /// One-line description of the app
+    pub summary: Option<String>,
+
+    /// Long description, paragraphs are separated by blank lines and lines
+    /// starting with `- ` are list items
+    pub description: Option<String>,
+
+    /// File with the long description, it takes precedence over `description`
+    pub description_file: Option<PathBuf>,
+    pub name_locale: Vec<(String, String)>,
+    pub summary_locale: Vec<(String, String)>,
+
+    /// Language the name and summary are written in, they are also tagged
+    /// with it
+    pub default_lang: Option<String>,
+
+    /// Reverse-DNS id like `com.example.App`, also the desktop file's name,
+    /// made from the executable if not set
+    pub app_id: Option<String>,
+
+    /// Umbrella project for the AppStream metadata, like `GNOME`
+    pub project_group: Option<String>,
+
+    /// Maintainer's email for the AppStream metadata
+    pub update_contact: Option<String>,
+
+    /// Project license, detected from the LICENSE file if not set
+    pub license: Option<License>,
+
+    /// License of the AppStream metadata itself
+    pub metadata_license: License,
+
+    /// Input methods the app works well with
+    pub supports: Vec<appstream::Control>,
+
+    /// OARS content rating, asked for in interactive mode if not set
+    pub rating_profile: Option<RatingProfile>,
+    pub no_appstream_validate: bool,
+
+    /// Always download remote targets instead of reusing a cached copy
+    pub no_cache: bool,
+
+    /// Fail instead of downloading anything, cached copies are still used
+    pub offline: bool,
+
+    /// Expected SHA-256 of a remote target
+    pub checksum: Option<String>,
+
+    /// Release to build for `gh:owner/repo` targets, the latest if not set
+    pub tag: Option<String>,
+
+    /// How long a download may take, no limit if not set
+    pub download_timeout: Option<Duration>,
+
+    /// How long an external command may run before it's killed
+    pub timeout: Duration,
+
+    /// How many bytes an archive may extract to
+    pub max_extract_size: u64,
+
+    /// Squashfs compressor for the AppImage, appimagetool's default if not set
+    pub compression: Option<Compression>,
+
+    /// Squashfs block size in KiB, a power of two from 4 to 1024
+    pub block_size: Option<u32>,
+
+    /// Extra arguments for mksquashfs, split on whitespace
+    pub mksquashfs_extra: Vec<String>,
+
+    /// Where AppImageUpdate looks for new versions, like
+    /// `gh-releases-zsync|user|repo|latest|App-*x86_64.AppImage.zsync`
+    pub update_information: Option<String>,
+
+    /// Sign the AppImage with gpg
+    pub sign: bool,
+
+    /// gpg key to sign with, implies `sign`, gpg's default key if not set
+    pub sign_key: Option<String>,
+
+    /// Stage everything but don't run the packaging tools
+    pub dry_run: bool,
+
+    /// Write the build's outcome here as JSON, `-` prints it
+    pub json_report: Option<PathBuf>,
+
+    /// Log each phase of the build and how long it took
+    pub timings: bool,
+
+    /// Also print what the external tools output when they succeed
+    pub verbose: bool,
+
+    /// Make the AppImage byte-identical across builds of the same input: file
+    /// times come from `SOURCE_DATE_EPOCH` (0 if unset) and owners are root
+    pub reproducible: bool,
+
+    /// Copy the desktop file, the AppStream metadata and the icon here instead
+    /// of producing an AppImage, only for folders and archives
+    pub export_metadata: Option<PathBuf>,
+
+    /// Copy the staged AppDir here instead of producing an AppImage, only for
+    /// folders, archives and Flatpak exports
+    pub appdir_out: Option<PathBuf>,
+
+    /// Overwrite the output AppImage if it already exists
+    pub force: bool,
+
+    /// Write the AppImage's SHA-256 next to it, as `<output>.sha256`
+    pub write_checksum: bool,
+
+    /// Fail instead of warning, like on the placeholder icon, missing
+    /// icoutils, a missing or unrecognized license, no main category, skipped
+    /// AppStream validation or a library that can't be bundled
+    pub strict: bool,
+
+    /// Toolbox container where Windows tools are run, `ubuntu-toolbox-22.04`
+    /// if not set
+    pub container: Option<String>,
+
+    /// Whether the user can be asked questions
+    pub interactive: bool,
+
+    /// A path or URL to a deb, a pkg2appimage descriptor, an archive or a
+    /// directory, or `gh:owner/repo` for a GitHub release
+    pub target: String,
+}
+
+impl Config {
+    pub fn new(target: impl Into<String>) -> Self {
+        Self {
+            terminal: false,
+            no_display: false,
+            hidden: false,
+            name: None,
+            generic_name: None,
+            try_exec: None,
+            exec: None,
+            app_version: None,
+            x_keys: Vec::new(),
+            categories: Vec::new(),
+            preset: None,
+            keywords: Vec::new(),
+            icon: None,
+            icon_url: None,
+            allow_default_icon: false,
+            default_icon: None,
+            bundle_libs: false,
+            bundle_extra: Vec::new(),
+            env: Vec::new(),
+            patch_interpreter: false,
+            summary: None,
+            description: None,
+            description_file: None,
+            name_locale: Vec::new(),
+            summary_locale: Vec::new(),
+            default_lang: None,
+            app_id: None,
+            project_group: None,
+            update_contact: None,
+            license: None,
+            metadata_license: License::CC0,
+            supports: Vec::new(),
+            rating_profile: None,
+            no_appstream_validate: false,
+            no_cache: false,
+            offline: false,
+            checksum: None,
+            tag: None,
+            download_timeout: None,
+            timeout: cmd::DEFAULT_TIMEOUT,
+            max_extract_size: archive::DEFAULT_MAX_SIZE,
+            compression: None,
+            block_size: None,
+            mksquashfs_extra: Vec::new(),
+            update_information: None,
+            sign: false,
+            sign_key: None,
+            dry_run: false,
+            export_metadata: None,
+            appdir_out: None,
+            json_report: None,
+            timings: false,
+            verbose: false,
+            reproducible: false,
+            force: false,
+            write_checksum: false,
+            strict: false,
+            container: None,
+            interactive: false,
+            target: target.into(),
+        }
+    }
+
+    fn signs(&self) -> bool {
+        self.sign || self.sign_key.is_some()
+    }
+
+    /// Whether appimagetool is run at all
+    fn packages(&self) -> bool {
+        !self.dry_run && self.export_metadata.is_none() && self.appdir_out.is_none()
+    }
+}
+
+/// Compressors appimagetool can build the squashfs with
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Compression {
+    Gzip,
+    Xz,
+    Zstd,
+}
+
+impl Compression {
+    pub const NAMES: [&'static str; 3] = ["gzip", "xz", "zstd"];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Compression::Gzip => "gzip",
+            Compression::Xz => "xz",
+            Compression::Zstd => "zstd",
+        }
+    }
+}
+
+impl FromStr for Compression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gzip" => Ok(Compression::Gzip),
+            "xz" => Ok(Compression::Xz),
+            "zstd" => Ok(Compression::Zstd),
+            _ => Err(format!("'{s}' is not a supported compression, use one of: {}", Self::NAMES.join(", "))),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct DesktopFile {
+    #[serde(rename = "Desktop Entry")]
+    file: DesktopEntry,
+}
+
+// The version of the desktop entry spec the files are written for
+const DESKTOP_SPEC_VERSION: &str = "1.5";
+
+// Just here for use with skip_serializing_if
+fn is_false(val: &bool) -> bool {
+    !*val
+}
+
+fn is_none_or_empty(val: &Option<String>) -> bool {
+    val.as_ref().is_none_or(String::is_empty)
+}
+
+#[derive(Serialize)]
+struct DesktopEntry {
+    #[serde(rename = "Name")]
+    name: String,
+    #[serde(rename = "GenericName")]
+    #[serde(skip_serializing_if = "is_none_or_empty")]
+    generic_name: Option<String>,
+    #[serde(rename = "Comment")]
+    #[serde(skip_serializing_if = "is_none_or_empty")]
+    comment: Option<String>,
+
+    // Localized variants of the keys above, written as `Key[locale]=value`
+    #[serde(flatten)]
+    translations: BTreeMap<&'static str, BTreeMap<String, String>>,
+    #[serde(rename = "TryExec")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    try_exec: Option<String>,
+    #[serde(rename = "Exec")]
+    exec: String,
+    #[serde(rename = "Icon")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    icon: Option<String>,
+    #[serde(rename = "Type")]
+    d_type: String,
+    #[serde(rename = "Version")]
+    #[serde(skip_serializing_if = "is_none_or_empty")]
+    version: Option<String>,
+    #[serde(rename = "Categories")]
+    categories: Vec<String>,
+    #[serde(rename = "Keywords")]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    keywords: Vec<String>,
+    #[serde(rename = "Terminal")]
+    #[serde(skip_serializing_if = "is_false")]
+    terminal: bool,
+    #[serde(rename = "NoDisplay")]
+    #[serde(skip_serializing_if = "is_false")]
+    no_display: bool,
+    #[serde(rename = "Hidden")]
+    #[serde(skip_serializing_if = "is_false")]
+    hidden: bool,
+
+    // Extension keys, like `X-AppImage-Version`
+    #[serde(flatten)]
+    extra: BTreeMap<String, String>,
+}
+
+#[derive(Serialize)]
+struct Pkg2AppimageDescriptor {
+    app: String,
+    ingredients: Pkg2AppimageDescriptorIngredients,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    script: Vec<String>,
+}
+
+#[derive(Default, Serialize)]
+struct Pkg2AppimageDescriptorIngredients {
+    dist: Option<String>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    sources: Vec<String>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    packages: Vec<String>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    script: Vec<String>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    debs: Vec<String>,
+}
+
+impl DesktopFile {
+    pub fn new(
+        name: String,
+        icon: Option<String>,
+        categories: Vec<String>,
+        terminal: bool,
+    ) -> Self {
+        Self {
+            file: DesktopEntry {
+                name,
+                generic_name: None,
+                comment: None,
+                translations: BTreeMap::new(),
+                try_exec: None,
+                exec: "./AppRun".to_string(),
+                d_type: "Application".to_string(),
+                version: Some(DESKTOP_SPEC_VERSION.to_string()),
+                icon,
+                categories,
+                keywords: Vec::new(),
+                terminal,
+                no_display: false,
+                hidden: false,
+                extra: BTreeMap::new(),
+            },
+        }
+    }
+
+    /// Writes the desktop file both in the AppDir root (where appimagetool
+    /// wants it) and in usr/share/applications (where AppStream looks for it),
+    /// returns the latter
+    fn write(&self, base_path: &Path, id: &str) -> Result<PathBuf, Error> {
+        let desktop = appstream::desktop_id(id);
+        let applications_path = base_path.join("usr").join("share").join("applications");
+        if !applications_path.exists() {
+            fs::create_dir_all(&applications_path)?;
+        }
+
+        desktop_entry::to_writer(File::create(base_path.join(&desktop))?, self)?;
+        fs::copy(base_path.join(&desktop), applications_path.join(&desktop))?;
+        Ok(applications_path.join(desktop))
+    }
+}
+
+/// Makes a reverse-DNS id out of an executable's name, AppStream only accepts
+/// ASCII alphanumerics, '-' and '_' inside each segment
+fn app_id(executable: &Path) -> String {
+    let stem = executable.file_stem().unwrap_or_default().to_string_lossy().to_lowercase();
+    let slug = stem
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '-' })
+        .collect::<String>();
+    let slug = slug.trim_matches('-');
+
+    if slug.is_empty() {
+        "com.to_appimage.app".to_string()
+    } else {
+        format!("com.to_appimage.{slug}")
+    }
+}
+
+#[derive(Copy, Clone, Default, Deserialize)]
+enum CliKind {
+    Native,
+    #[default]
+    Toolbox,
+}
+
+#[derive(Deserialize)]
+struct CliConf {
+    #[serde(default)]
+    kind: CliKind,
+
+    #[serde(default = "default_container_name")]
+    container_name: String,
+
+    /// Glob patterns picking the executable when there are several, tried in
+    /// order
+    #[serde(default)]
+    prefer_executables: Vec<String>,
+
+    /// Extra category presets, they replace built-in ones with the same name
+    #[serde(default)]
+    presets: BTreeMap<String, Vec<String>>,
+
+    /// Extra names to find a tool by, like `appimagetool: [my-appimagetool]`,
+    /// they are tried before the built-in ones
+    #[serde(default)]
+    tools: BTreeMap<String, Vec<String>>,
+
+    /// Folders where tools are looked for when they aren't in PATH, before
+    /// the usual ones like `~/.local/bin`
+    #[serde(default)]
+    tool_dirs: Vec<PathBuf>,
+}
+
+fn default_container_name() -> String {
+    "ubuntu-toolbox-22.04".to_string()
+}
+
+impl Default for CliConf {
+    fn default() -> CliConf {
+        CliConf {
+            kind: CliKind::Toolbox,
+            container_name: default_container_name(),
+            prefer_executables: Vec::new(),
+            presets: BTreeMap::new(),
+            tools: BTreeMap::new(),
+            tool_dirs: Vec::new(),
+        }
+    }
+}
+
+const PRESETS: &[(&str, &[&str])] = &[
+    ("game", &["Game"]),
+    ("dev", &["Development", "IDE"]),
+    ("multimedia", &["AudioVideo", "Audio", "Video"]),
+    ("graphics", &["Graphics"]),
+    ("office", &["Office"]),
+    ("network", &["Network"]),
+];
+
+/// Desktop menus sort apps by these, an app needs at least one of them
+const MAIN_CATEGORIES: &[&str] = &[
+    "AudioVideo", "Audio", "Video", "Development", "Education", "Game", "Graphics", "Network", "Office", "Science",
+    "Settings", "System", "Utility",
+];
+
+/// Names each tool is usually installed under, tried in order
+const TOOL_NAMES: &[(&str, &[&str])] = &[
+    ("appimagetool", &["appimagetool", "appimagetool-x86_64.AppImage", "appimagetool-aarch64.AppImage", "appimagetool.appimage"]),
+    ("pkg2appimage", &["pkg2appimage", "pkg2appimage-x86_64.AppImage", "gearlever_pkg2appimage_02a375.appimage"]),
+    ("wrestool", &["wrestool"]),
+    ("icotool", &["icotool"]),
+];
+
+/// Where AppImage tools usually end up when they aren't installed system-wide,
+/// relative to the home folder unless absolute
+const TOOL_DIRS: [&str; 3] = [".local/bin", "Applications", "/opt"];
+
+impl CliConf {
+    /// Folders searched for tools missing from PATH, the configured ones first
+    fn tool_dirs(&self) -> Vec<PathBuf> {
+        let home = std::env::var_os("HOME").map(PathBuf::from);
+        let built_in = TOOL_DIRS.iter().filter_map(|dir| match Path::new(dir).is_absolute() {
+            true => Some(PathBuf::from(dir)),
+            false => home.as_ref().map(|home| home.join(dir)),
+        });
+
+        self.tool_dirs.iter().cloned().chain(built_in).collect()
+    }
+
+    /// Names `tool` may be installed under, the configured ones first
+    fn tool_names(&self, tool: &str) -> Vec<String> {
+        let configured = self.tools.get(tool).into_iter().flatten().cloned();
+        let built_in = TOOL_NAMES
+            .iter()
+            .filter(|(name, _)| *name == tool)
+            .flat_map(|(_, names)| names.iter().map(|n| n.to_string()));
+
+        configured.chain(built_in).unique().collect()
+    }
+
+    fn preset(&self, name: &str) -> Option<Vec<String>> {
+        self.presets.get(name).cloned().or_else(|| {
+            PRESETS
+                .iter()
+                .find(|(preset, _)| *preset == name)
+                .map(|(_, categories)| categories.iter().map(|c| c.to_string()).collect())
+        })
+    }
+
+    /// The preset's categories followed by the explicit ones, without
+    /// duplicates, `shipped` ones are used when there are none
+    fn categories(&self, config: &Config, shipped: &[String]) -> Result<Vec<String>, Error> {
+        let preset = match &config.preset {
+            Some(name) => self.preset(name).ok_or_else(|| Error::UnknownPreset(name.clone()))?,
+            None => Vec::new(),
+        };
+
+        let categories: Vec<String> = preset.into_iter().chain(config.categories.iter().cloned()).unique().collect();
+        if categories.is_empty() && !shipped.is_empty() {
+            Ok(shipped.to_vec())
+        } else if categories.is_empty() {
+            Ok(vec!["Utility".to_string()])
+        } else {
+            Ok(categories)
+        }
+    }
+
+    /// Makes sure the container exists before running anything inside it,
+    /// `exists` asks the container backend
+    fn ensure_container<F>(&self, exists: F) -> Result<(), Error>
+    where
+        F: FnOnce(&str) -> Result<bool, Error>,
+    {
+        if matches!(self.kind, CliKind::Native) || exists(&self.container_name)? {
+            Ok(())
+        } else {
+            Err(Error::ContainerNotFound(self.container_name.clone()))
+        }
+    }
+}
+
+/// The icoutils tools that aren't installed, inside a container they can't be
+/// looked for so they are taken as there
+fn missing_icon_tools<F: Fn(&str) -> bool>(conf: &CliConf, exists: F) -> Vec<&'static str> {
+    [("wrestool", conf.kind), ("icotool", CliKind::Native)]
+        .into_iter()
+        .filter(|(tool, kind)| matches!(kind, CliKind::Native) && cmd::resolve(&conf.tool_names(tool), &exists).is_none())
+        .map(|(tool, _)| tool)
+        .collect()
+}
+
+/// Takes the icon from a Windows executable, without the tools for it the
+/// placeholder is used instead. Returns whether it was the placeholder
+fn exe_icon<F: Fn(&str) -> bool>(conf: &CliConf, config: &Config, appdir: &Path, exe: &Path, exists: F) -> Result<bool, Error> {
+    let missing = missing_icon_tools(conf, exists);
+    if missing.is_empty() {
+        extract_icon_from_exe(conf, config, appdir, &exe.to_string_lossy())?;
+        return Ok(false);
+    }
+
+    warn(config, &format!("{} isn't installed so the icon can't be taken from the .exe, install icoutils", missing.join(" and ")))?;
+    write_default_icon(appdir, config)?;
+    Ok(true)
+}
+
+fn extract_icon_from_exe(conf: &CliConf, config: &Config, dir: &Path, file: &str) -> Result<(), Error> {
+    conf.ensure_container(cmd::container_exists)?;
+
+    cmd::app_from(&conf.tool_names("wrestool"), conf.kind, Some(&conf.container_name), &conf.tool_dirs())?
+        .arg("-x")
+        .arg("--output=icon.ico")
+        .arg("-t")
+        .arg("14")
+        .arg(file)
+        .run(config)?;
+
+    cmd::app_from(&conf.tool_names("icotool"), CliKind::Native, Some(&conf.container_name), &conf.tool_dirs())?
+        .arg("-x")
+        .arg("icon.ico")
+        .arg("-h")
+        .arg("256")
+        .arg("-o")
+        .arg(dir.join("AppIcon.png"))
+        .run_outerr(config)?;
+
+    std::fs::remove_file("icon.ico")?;
+    Ok(())
+}
+
+/// Warns about executable preferences that aren't valid patterns
+fn check_preferences(config: &Config, preferences: &[String]) -> Result<(), Error> {
+    for p in preferences {
+        if let Err(e) = glob::Pattern::new(p) {
+            warn(config, &format!("ignoring executable preference '{p}': {e}"))?;
+        }
+    }
+    Ok(())
+}
+
+/// Names usually given to the script that starts an app
+const LAUNCHER_NAMES: [&str; 3] = ["run", "start", "launch"];
+
+/// Finds a file with `ext` in `path`, one named after the folder or like a
+/// launcher is preferred, otherwise the first one alphabetically
+fn look_for_ext(path: &Path, ext: &str) -> Option<PathBuf> {
+    let files = visible_files(path)
+        .into_iter()
+        .filter(|p| {
+            p.extension()
+                .map(|e| e.to_str().unwrap_or(""))
+                .unwrap_or("")
+                == ext
+        })
+        .sorted()
+        .collect::<Vec<_>>();
+
+    let stem = |p: &PathBuf| p.file_stem().unwrap_or_default().to_string_lossy().to_lowercase();
+    let folder = path.file_name().unwrap_or_default().to_string_lossy().to_lowercase();
+
+    files
+        .iter()
+        .find(|p| stem(p) == folder)
+        .or_else(|| files.iter().find(|p| LAUNCHER_NAMES.contains(&stem(p).as_str())))
+        .or(files.first())
+        .cloned()
+}
+
+fn look_for_no_exts(path: &Path) -> Result<Vec<PathBuf>, Error> {
+    fn is_exe_no_ext(p: &Path) -> Result<bool, Error> {
+        let file_name_lower = p
+            .file_name()
+            .ok_or_else(|| Error::ExecutableNotFound(p.display().to_string()))?
+            .to_string_lossy()
+            .to_lowercase();
+        Ok(p.extension().is_none() && !["legal_details", "license", "readme", "apprun"].contains(&file_name_lower.as_str()))
+    }
+
+    let mut exes = Vec::new();
+    for file in visible_files(path) {
+        if is_exe_no_ext(&file)? {
+            exes.push(file);
+        }
+    }
+    Ok(exes)
+}
+
+// Dotfiles like `.DirIcon` or `.install` are never what we look for
+fn is_hidden(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.'))
+}
+
+/// The entries of `dir` sorted by name, `read_dir`'s order depends on the
+/// filesystem
+fn sorted_entries(dir: &Path) -> io::Result<Vec<fs::DirEntry>> {
+    let mut entries = fs::read_dir(dir)?.collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by_key(fs::DirEntry::file_name);
+    Ok(entries)
+}
+
+/// The regular files right inside `dir`, without hidden ones
+fn visible_files(dir: &Path) -> Vec<PathBuf> {
+    sorted_entries(dir)
+        .into_iter()
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.is_file() && !is_hidden(p))
+        .collect()
+}
+
+/// Looks for the icon referenced by a `.desktop` file shipped with the app,
+/// either at the root or in `usr/share/applications`
+fn bundled_desktop_icon(appdir: &Path) -> Option<PathBuf> {
+    [appdir.to_path_buf(), appdir.join("usr").join("share").join("applications")]
+        .iter()
+        .flat_map(|dir| visible_files(dir))
+        .filter(|p| p.is_ext("desktop"))
+        .filter_map(|p| fs::read_to_string(p).ok())
+        .filter_map(|contents| desktop_entry::get_value(&contents, "Desktop Entry", "Icon").map(str::to_string))
+        .find_map(|icon| resolve_icon(appdir, &icon))
+}
+
+/// Finds an icon by name inside the AppDir's hicolor theme, pixmaps or root,
+/// scalable icons are preferred and then the biggest ones
+fn resolve_icon(appdir: &Path, icon: &str) -> Option<PathBuf> {
+    const EXTS: [&str; 2] = ["png", "svg"];
+
+    let path = Path::new(icon);
+    let has_ext = EXTS.iter().any(|ext| path.is_ext(ext));
+    if has_ext {
+        // Absolute paths are taken as relative to the AppDir
+        let in_tree = appdir.join(path.strip_prefix("/").unwrap_or(path));
+        if in_tree.is_file() {
+            return Some(in_tree);
+        }
+    }
+
+    // Names like "org.example.App" have dots that aren't extensions
+    let name = if has_ext { path.file_stem() } else { path.file_name() }?.to_string_lossy();
+
+    let hicolor = appdir.join("usr").join("share").join("icons").join("hicolor");
+    let themed = fs::read_dir(hicolor)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|size_dir| {
+            let size = size_dir.file_name().to_string_lossy().into_owned();
+            let rank = if size == "scalable" { u32::MAX } else { size.split_once('x')?.0.parse().ok()? };
+            EXTS.iter()
+                .map(|ext| size_dir.path().join("apps").join(format!("{name}.{ext}")))
+                .find(|p| p.is_file())
+                .map(|p| (rank, p))
+        })
+        .max_by_key(|(rank, _)| *rank)
+        .map(|(_, p)| p);
+
+    themed.or_else(|| {
+        [appdir.join("usr").join("share").join("pixmaps"), appdir.to_path_buf()]
+            .iter()
+            .flat_map(|dir| EXTS.map(|ext| dir.join(format!("{name}.{ext}"))))
+            .find(|p| p.is_file())
+    })
+}
+
+/// The first candidate whose file name matches a preference, earlier
+/// preferences win. Invalid ones are skipped, `check_preferences` warns
+/// about them
+fn preferred_executable(candidates: &[PathBuf], preferences: &[String]) -> Option<PathBuf> {
+    preferences
+        .iter()
+        .filter_map(|p| glob::Pattern::new(p).ok())
+        .find_map(|pattern| {
+            candidates
+                .iter()
+                .find(|c| c.file_name().is_some_and(|n| pattern.matches(&n.to_string_lossy())))
+                .cloned()
+        })
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("unsupported archive format '{0}', extract it yourself and pass the directory instead")]
+    ArchiveFormatNotSupported(String),
+
+    #[error("pkg2appimage didn't produce any AppImage, check its output inside 'out'")]
+    NoAppImageProduced,
+
+    #[error("couldn't find any suitable executable, make sure the program is at the top of the directory")]
+    NoExecutable,
+
+    #[error("'{}' doesn't exist, check the path", path.display())]
+    InputNotFound { path: PathBuf },
+
+    #[error("'{}' can't be read, check its permissions", path.display())]
+    InputNotReadable { path: PathBuf },
+
+    #[error("'{0}' is not a file inside the app, --exec is relative to the app's directory")]
+    ExecutableNotFound(String),
+
+    #[error("couldn't find '{0}', install it and try again")]
+    ToolNotFound(String),
+
+    #[error("'{0}' is not a language tag, use something like 'es' or 'pt-BR'")]
+    InvalidLanguage(String),
+
+    #[error("'{0}' is not valid update information, use `zsync|<url>`, `gh-releases-zsync|<user>|<repo>|<tag>|<file>` or `pling-v1-zsync|<id>|<file>`")]
+    InvalidUpdateInformation(String),
+
+    #[error("couldn't read the description file '{}': {source}, check the path", path.display())]
+    DescriptionFile { path: PathBuf, source: std::io::Error },
+
+    #[error("'{0}' is not a valid app id, use a reverse-DNS name like `com.example.App` made of letters, digits, '_' and '-'")]
+    InvalidAppId(String),
+
+    #[error("a block size of {0} KiB isn't supported by squashfs, use a power of two from 4 to 1024")]
+    InvalidBlockSize(u32),
+
+    #[error("'{0}' is not a valid extension key, they start with `X-` and only have letters, digits and '-'")]
+    InvalidExtensionKey(String),
+
+    #[error("can't bundle '{spec}': {reason}, use --bundle-extra <glob>:<directory inside the AppDir>")]
+    InvalidBundleExtra { spec: String, reason: String },
+
+    #[error("'{0}' is not a valid environment variable name, use letters, digits and '_' without a leading digit")]
+    InvalidEnvName(String),
+
+    #[error("'{}' is not valid UTF-8, rename it", .0.display())]
+    NonUtf8Path(PathBuf),
+
+    #[error("there's no home folder to keep {0} in, install it yourself")]
+    NoHomeDir(String),
+
+    #[error("'{0}' is not a GitHub repository, use gh:owner/repo")]
+    InvalidGitHubRepo(String),
+
+    #[error("there's no '{0}' preset, try one of: game, dev, multimedia, graphics, office or network")]
+    UnknownPreset(String),
+
+    #[error("{0}, raise --max-extract-size if it's legitimate")]
+    ArchiveTooLarge(String),
+
+    #[error("the container '{0}' doesn't exist, create it with `toolbox create {0}` or pick another one with --container")]
+    ContainerNotFound(String),
+
+    #[error("the tools have to run in a container but none was given, pick one with --container")]
+    NoContainer,
+
+    #[error("there's no available program for downloading files, install curl or wget")]
+    NoDownloader,
+
+    #[error("signing needs gpg, install it or build without --sign")]
+    GpgNotFound,
+
+    #[error("couldn't download {url}: {reason}")]
+    Http { url: String, reason: String },
+
+    #[error("{0} would have to be downloaded but --offline is set, fetch it beforehand")]
+    Offline(String),
+
+    #[error("nothing was downloaded from {0}, check the URL")]
+    DownloadFailed(String),
+
+    #[error("GitHub's rate limit was hit, set {} to a token to raise it", github::TOKEN_VAR)]
+    GitHubRateLimited,
+
+    #[error("gh:{0} needs the `net` feature to ask GitHub for its releases, download an asset yourself and pass it instead")]
+    GitHubWithoutNet(String),
+
+    #[error("release {tag} of {repo} has no Linux asset for this architecture, download one yourself and pass it instead")]
+    NoReleaseAsset { repo: String, tag: String },
+
+    #[error("the download's checksum is {actual} but {expected} was expected, check the URL and the checksum")]
+    ChecksumMismatch { expected: String, actual: String },
+
+    #[error("architecture '{0}' is not supported, only x86_64 and aarch64 are")]
+    ArchitectureNotSupported(String),
+
+    #[error("'{program}' failed\n{output}")]
+    CommandFailed { program: String, output: String },
+
+    #[error("'{program}' didn't finish in time and was stopped, raise --timeout if it needs longer")]
+    CommandTimedOut { program: String },
+
+    #[error("the operation was cancelled")]
+    Cancelled,
+
+    #[error("the archive entry '{path}' would be written outside of the extraction directory, the archive might be malicious")]
+    UnsafeArchiveEntry { path: String },
+
+    #[error("'{0}' already exists, use --force to overwrite it")]
+    OutputExists(PathBuf),
+
+    #[error("no icon was found and strict builds don't use the placeholder one, pass --icon or --allow-default-icon")]
+    DefaultIcon,
+
+    #[error("the default icon '{}' can't be used: {reason}, pass an image with --default-icon", path.display())]
+    InvalidDefaultIcon { path: PathBuf, reason: String },
+
+    #[error("{0}, --strict builds stop on this warning")]
+    Strict(String),
+
+    #[error("{0}, add a LICENSE file to the app or pass --license")]
+    License(#[from] licensing::Error),
+
+    #[error("{0}, use --no-appstream-validate to skip this check")]
+    AppStream(#[from] appstream::Error),
+
+    #[error("couldn't write the desktop file: {0}")]
+    DesktopEntry(#[from] desktop_entry::Error),
+
+    #[error("the desktop file is not valid:\n{0}")]
+    InvalidDesktopFile(String),
+
+    #[error("the Flatpak export {0}, make sure it was finished with `flatpak build-finish`")]
+    FlatpakExport(String),
+
+    #[error("couldn't write the pkg2appimage descriptor: {0}")]
+    Descriptor(#[from] serde_yaml::Error),
+
+    #[error("couldn't show a dialog: {0}")]
+    Dialog(String),
+
+    #[error("couldn't extract the archive: {0}")]
+    Zip(#[from] zip::result::ZipError),
+
+    #[error("couldn't process the icon: {0}")]
+    Image(#[from] image::ImageError),
+
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+}
+
+// dialog's errors don't implement std::error::Error, so only their message is
+// kept
+impl From<dialog::Error> for Error {
+    fn from(e: dialog::Error) -> Self {
+        Error::Dialog(e.to_string())
+    }
+}
+
+impl Error {
+    /// The process exit code for this error, grouped by category
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Error::ArchiveFormatNotSupported(_)
+            | Error::InputNotFound { .. }
+            | Error::InputNotReadable { .. }
+            | Error::UnsafeArchiveEntry { .. }
+            | Error::ArchiveTooLarge(_)
+            | Error::NoExecutable
+            | Error::ExecutableNotFound(_)
+            | Error::Cancelled
+            | Error::OutputExists(_)
+            | Error::DefaultIcon
+            | Error::InvalidDefaultIcon { .. }
+            | Error::UnknownPreset(_)
+            | Error::InvalidLanguage(_)
+            | Error::InvalidUpdateInformation(_)
+            | Error::InvalidBlockSize(_)
+            | Error::DescriptionFile { .. }
+            | Error::InvalidAppId(_)
+            | Error::InvalidExtensionKey(_)
+            | Error::InvalidEnvName(_)
+            | Error::InvalidBundleExtra { .. }
+            | Error::NonUtf8Path(_)
+            | Error::InvalidGitHubRepo(_) => 2,
+            Error::NoAppImageProduced
+            | Error::ToolNotFound(_)
+            | Error::ContainerNotFound(_)
+            | Error::NoContainer
+            | Error::NoDownloader
+            | Error::NoHomeDir(_)
+            | Error::GpgNotFound
+            | Error::Http { .. }
+            | Error::DownloadFailed(_)
+            | Error::Offline(_)
+            | Error::GitHubRateLimited
+            | Error::GitHubWithoutNet(_)
+            | Error::NoReleaseAsset { .. }
+            | Error::ChecksumMismatch { .. }
+            | Error::ArchitectureNotSupported(_)
+            | Error::CommandFailed { .. }
+            | Error::CommandTimedOut { .. } => 3,
+            Error::Strict(_)
+            | Error::License(_)
+            | Error::AppStream(_)
+            | Error::DesktopEntry(_)
+            | Error::InvalidDesktopFile(_)
+            | Error::FlatpakExport(_)
+            | Error::Descriptor(_) => 4,
+            Error::Dialog(_) | Error::Image(_) | Error::Zip(_) | Error::Io(_) => 5,
+        }
+    }
+}
+
+mod archive {
+    use crate::{cmd, Error};
+    use itertools::Itertools;
+    use path_utils::PathExt;
+    use std::{
+        fs::{self, File, Permissions},
+        io::{self, Read, Write},
+        os::unix::fs::{symlink, PermissionsExt},
+        path::{Component, Path, PathBuf},
+        process::{Command, Stdio},
+        time::Duration,
+    };
+
+    const S_IFMT: u32 = 0o170000;
+    const S_IFLNK: u32 = 0o120000;
+
+    // Longest symlink target Linux takes
+    const PATH_MAX: u64 = 4096;
+
+    pub const DEFAULT_MAX_SIZE: u64 = 4 * 1024 * 1024 * 1024;
+    const DEFAULT_MAX_RATIO: u64 = 100;
+
+    // Small archives can compress far better than big ones without being
+    // bombs
+    const RATIO_GRACE: u64 = 16 * 1024 * 1024;
+
+    /// How much an archive may extract to
+    #[derive(Clone, Copy, Debug)]
+    pub struct Limits {
+        pub max_size: u64,
+
+        /// Extracted size divided by the archive's size
+        pub max_ratio: u64,
+
+        /// How long the external tools may take
+        pub timeout: Duration,
+    }
+
+    impl Default for Limits {
+        fn default() -> Self {
+            Self {
+                max_size: DEFAULT_MAX_SIZE,
+                max_ratio: DEFAULT_MAX_RATIO,
+                timeout: cmd::DEFAULT_TIMEOUT,
+            }
+        }
+    }
+
+    // Keeps count of what has been extracted so far
+    struct Budget {
+        limits: Limits,
+        compressed: u64,
+        extracted: u64,
+    }
+
+    impl Budget {
+        fn new(input: &Path, limits: Limits) -> io::Result<Self> {
+            Ok(Self {
+                limits,
+                compressed: fs::metadata(input)?.len(),
+                extracted: 0,
+            })
+        }
+
+        fn check(&self, extra: u64) -> Result<(), Error> {
+            let extracted = self.extracted.saturating_add(extra);
+            if extracted > self.limits.max_size {
+                Err(Error::ArchiveTooLarge(format!("the archive extracts to more than {} bytes", self.limits.max_size)))
+            } else if extracted > RATIO_GRACE && extracted / self.compressed.max(1) > self.limits.max_ratio {
+                Err(Error::ArchiveTooLarge(format!(
+                    "the archive is more than {} times smaller than its contents, it might be a zip bomb",
+                    self.limits.max_ratio
+                )))
+            } else {
+                Ok(())
+            }
+        }
+
+        fn take(&mut self, size: u64) -> Result<(), Error> {
+            self.check(size)?;
+            self.extracted += size;
+            Ok(())
+        }
+
+        // Sizes in headers can lie, so this stops at the limit whatever the
+        // entry claims
+        fn copy<R: Read, W: Write>(&mut self, reader: R, writer: &mut W) -> Result<(), Error> {
+            let allowed = self.limits.max_size.saturating_sub(self.extracted);
+            let copied = io::copy(&mut reader.take(allowed.saturating_add(1)), writer)?;
+            self.take(copied)
+        }
+    }
+
+    // External tools can extract everything to stdout instead, which
+    // measures the contents without writing them
+    fn check_streamed(to_stdout: &mut Command, budget: &Budget) -> Result<(), Error> {
+        let mut child = to_stdout.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::null()).spawn()?;
+        let allowed = budget.limits.max_size.saturating_sub(budget.extracted).saturating_add(1);
+        let size = match child.stdout.take() {
+            Some(stdout) => io::copy(&mut stdout.take(allowed), &mut io::sink())?,
+            None => 0,
+        };
+        // It's still running if it was cut off
+        let _ = child.kill();
+        child.wait()?;
+        budget.check(size)
+    }
+
+    // What external tools extracted is measured again afterwards, for the
+    // AppImage runtime it's the only check
+    fn dir_size(dir: &Path) -> io::Result<u64> {
+        let mut size = 0;
+        for entry in fs::read_dir(dir)?.flatten() {
+            let metadata = entry.path().symlink_metadata()?;
+            size += if metadata.is_dir() { dir_size(&entry.path())? } else { metadata.len() };
+        }
+        Ok(size)
+    }
+
+    pub fn is_archive(path: &Path) -> bool {
+        Archive::guess(path).is_ok()
+    }
+
+    enum Archive {
+        Zip,
+        Tar,
+        TarGz,
+        // Compressions we don't handle natively go to the tar tool
+        ExternalTar,
+        SevenZip,
+        // AppImages extract themselves
+        AppImage,
+    }
+
+    impl Archive {
+        fn guess<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+            if path.as_ref().extension().is_some_and(|e| e.eq_ignore_ascii_case("appimage")) {
+                return Ok(Archive::AppImage);
+            }
+
+            // Due to how this works, the extensions are reversed, that's why
+            // they are written this way
+            match path.as_ref().extensions_lossy().join(".").as_str() {
+                "zip" => Ok(Archive::Zip),
+                "tar" => Ok(Archive::Tar),
+                "gz.tar" | "tgz" => Ok(Archive::TarGz),
+                "bz2.tar" | "xz.tar" | "tbz2" | "tbz" | "txz" => Ok(Archive::ExternalTar),
+                "7z" => Ok(Archive::SevenZip),
+                a => Err(Error::ArchiveFormatNotSupported(a.to_string())),
+            }
+        }
+    }
+
+    // Entries can't get out of the output directory either with '..' or by
+    // being written through a symlink extracted before them
+    fn safe_path(output: &Path, name: Option<PathBuf>, raw_name: &str) -> Result<PathBuf, Error> {
+        let unsafe_entry = || Error::UnsafeArchiveEntry { path: raw_name.to_string() };
+        let path = output.join(name.ok_or_else(unsafe_entry)?);
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+            if !parent.canonicalize()?.starts_with(output.canonicalize()?) {
+                return Err(unsafe_entry());
+            }
+        }
+        Ok(path)
+    }
+
+    // A link leading outside the output would let a later entry with its
+    // name be written there, so its target has to stay inside
+    fn safe_link(output: &Path, link: &Path, target: &str, raw_name: &str) -> Result<(), Error> {
+        let resolved = normalize(&link.parent().unwrap_or(output).join(target));
+        if Path::new(target).is_absolute() || !resolved.starts_with(normalize(output)) {
+            return Err(Error::UnsafeArchiveEntry { path: raw_name.to_string() });
+        }
+        Ok(())
+    }
+
+    // Never writes through what's already there, like a symlink extracted
+    // before
+    fn create_new(path: &Path, raw_name: &str) -> Result<File, Error> {
+        File::options().write(true).create_new(true).open(path).map_err(|e| match e.kind() {
+            io::ErrorKind::AlreadyExists => Error::UnsafeArchiveEntry { path: raw_name.to_string() },
+            _ => e.into(),
+        })
+    }
+
+    fn unzip(input: &Path, output: &Path, budget: &mut Budget) -> Result<(), Error> {
+        let mut archive = zip::ZipArchive::new(File::open(input)?)?;
+
+        // Refuse early with what the archive says it holds
+        let mut declared = 0u64;
+        for i in 0..archive.len() {
+            declared = declared.saturating_add(archive.by_index_raw(i)?.size());
+        }
+        budget.check(declared)?;
+
+        for i in 0..archive.len() {
+            let mut entry = archive.by_index(i)?;
+            let path = safe_path(output, entry.enclosed_name(), entry.name())?;
+
+            if entry.is_dir() {
+                fs::create_dir_all(&path)?;
+                continue;
+            }
+
+            // Symlinks are stored as files whose contents are the target
+            if entry.unix_mode().is_some_and(|m| m & S_IFMT == S_IFLNK) {
+                let mut target = String::new();
+                let read = entry.by_ref().take(PATH_MAX + 1).read_to_string(&mut target)? as u64;
+                budget.take(read)?;
+                if read > PATH_MAX {
+                    return Err(Error::UnsafeArchiveEntry { path: entry.name().to_string() });
+                }
+                safe_link(output, &path, &target, entry.name())?;
+                symlink(target, &path)?;
+                continue;
+            }
+
+            let mut file = create_new(&path, entry.name())?;
+            budget.copy(&mut entry, &mut file)?;
+
+            if let Some(mode) = entry.unix_mode() {
+                fs::set_permissions(&path, Permissions::from_mode(mode))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn untar<R: Read>(reader: R, output: &Path, budget: &mut Budget) -> Result<(), Error> {
+        let mut archive = tar::Archive::new(reader);
+        archive.set_preserve_permissions(true);
+
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let path = entry.path()?.into_owned();
+            if path.components().any(|c| matches!(c, Component::ParentDir | Component::RootDir | Component::Prefix(_))) {
+                return Err(Error::UnsafeArchiveEntry { path: path.to_string_lossy().into_owned() });
+            }
+
+            // Unlike zip, a tar entry's data is exactly as long as its header
+            // says
+            budget.take(entry.size())?;
+
+            // unpack_in also refuses to write through symlinks that lead
+            // outside of the output
+            if !entry.unpack_in(output)? {
+                return Err(Error::UnsafeArchiveEntry { path: path.to_string_lossy().into_owned() });
+            }
+        }
+        Ok(())
+    }
+
+    // Resolves '.' and '..' without touching the filesystem, so that it works
+    // with dangling links
+    fn normalize(path: &Path) -> PathBuf {
+        let mut res = PathBuf::new();
+        for component in path.components() {
+            match component {
+                Component::ParentDir => {
+                    res.pop();
+                }
+                Component::CurDir => {}
+                c => res.push(c),
+            }
+        }
+        res
+    }
+
+    /// Returns the symlinks inside `root` that are dangling or point outside
+    /// of it, those won't work once packaged
+    pub fn broken_symlinks(root: &Path) -> io::Result<Vec<PathBuf>> {
+        let mut broken = Vec::new();
+        for entry in fs::read_dir(root)?.flatten() {
+            let path = entry.path();
+            let file_type = entry.file_type()?;
+            if file_type.is_symlink() {
+                let target = normalize(&path.parent().unwrap_or(root).join(fs::read_link(&path)?));
+                if !target.starts_with(normalize(root)) || fs::symlink_metadata(&target).is_err() {
+                    broken.push(path);
+                }
+            } else if file_type.is_dir() {
+                broken.extend(broken_symlinks(&path)?);
+            }
+        }
+        Ok(broken)
+    }
+
+    /// Extracts `input` inside `output` within `limits`. What external tools
+    /// extract is measured before anything is written, except for AppImages,
+    /// which are only checked once extracted
+    pub fn unarchive<P2>(input: &Path, output: P2, limits: &Limits) -> Result<(), Error>
+    where
+        P2: AsRef<Path>,
+    {
+        let mut budget = Budget::new(input, *limits)?;
+        match Archive::guess(input)? {
+            Archive::Zip => unzip(input, output.as_ref(), &mut budget),
+            Archive::Tar => untar(File::open(input)?, output.as_ref(), &mut budget),
+            Archive::TarGz => untar(flate2::read::GzDecoder::new(File::open(input)?), output.as_ref(), &mut budget),
+            // GNU tar already skips members with '..' and strips leading '/'
+            Archive::ExternalTar => {
+                let tar = || cmd::app("tar").ok_or_else(|| Error::ToolNotFound("tar".to_string()));
+                check_streamed(tar()?.arg("-xOf").arg(input), &budget)?;
+                cmd::run_with(tar()?.arg("-xf").arg(input).arg("-C").arg(output.as_ref()), limits.timeout)?;
+                budget.take(dir_size(output.as_ref())?)
+            }
+            Archive::SevenZip => {
+                let seven_zip = || cmd::app("7z").ok_or_else(|| Error::ToolNotFound("7z".to_string()));
+                check_streamed(seven_zip()?.arg("x").arg("-so").arg(input), &budget)?;
+                cmd::run_with(seven_zip()?.arg("x").arg(input).arg(format!("-o{}", output.as_ref().display())), limits.timeout)?;
+                budget.take(dir_size(output.as_ref())?)
+            }
+            // Leaves a lone squashfs-root directory, which is then entered.
+            // The runtime can't extract to stdout, so its size is only known
+            // once it's on disk
+            Archive::AppImage => {
+                let appimage = input.canonicalize()?;
+                crate::mark_executable(&appimage)?;
+                cmd::run_with(Command::new(appimage).arg("--appimage-extract").current_dir(output.as_ref()), limits.timeout)?;
+                budget.take(dir_size(output.as_ref())?)
+            }
+        }
+    }
+}
+
+mod deb {
+    use std::{
+        fs,
+        io::{self, Read},
+        path::Path,
+    };
+
+    use crate::{cmd, desktop_entry, Error};
+
+    const AR_MAGIC: &[u8] = b"!<arch>\n";
+    const AR_HEADER_LEN: usize = 60;
+
+    /// Packages every system has, bundling them does more harm than good
+    const BASE_PACKAGES: [&str; 14] = [
+        "libc6",
+        "libc-bin",
+        "libgcc1",
+        "libgcc-s1",
+        "libstdc++6",
+        "dpkg",
+        "debconf",
+        "coreutils",
+        "bash",
+        "dash",
+        "perl-base",
+        "lsb-base",
+        "init-system-helpers",
+        "sensible-utils",
+    ];
+
+    fn invalid(msg: &str) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, msg)
+    }
+
+    /// The `<prefix>*` member of the deb's ar archive, like `control.tar.gz`,
+    /// with its name
+    fn member<'a>(data: &'a [u8], prefix: &str) -> io::Result<(&'a str, &'a [u8])> {
+        let mut rest = data.strip_prefix(AR_MAGIC).ok_or_else(|| invalid("not a deb package"))?;
+        while rest.len() >= AR_HEADER_LEN {
+            let (header, body) = rest.split_at(AR_HEADER_LEN);
+            let name = std::str::from_utf8(&header[..16]).map_err(|_| invalid("corrupted deb package"))?.trim_end().trim_end_matches('/');
+            let size: usize = std::str::from_utf8(&header[48..58])
+                .ok()
+                .and_then(|s| s.trim().parse().ok())
+                .filter(|size| *size <= body.len())
+                .ok_or_else(|| invalid("corrupted deb package"))?;
+
+            if name.starts_with(prefix) {
+                return Ok((name, &body[..size]));
+            }
+            // Members are aligned to 2 bytes
+            rest = &body[(size + size % 2).min(body.len())..];
+        }
+
+        Err(invalid(&format!("the deb package has no {prefix} archive")))
+    }
+
+    fn control_from_tar<R: Read>(reader: R) -> io::Result<String> {
+        let mut archive = tar::Archive::new(reader);
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            if entry.path()?.file_name().is_some_and(|n| n == "control") {
+                let mut control = String::new();
+                entry.read_to_string(&mut control)?;
+                return Ok(control);
+            }
+        }
+
+        Err(invalid("the deb package has no control file"))
+    }
+
+    /// Reads the control file of a deb, gzip and uncompressed control
+    /// archives are read directly and `dpkg-deb` is used for the rest
+    pub fn control(deb: &Path) -> Result<String, Error> {
+        let data = fs::read(deb)?;
+        match member(&data, "control.tar")? {
+            ("control.tar.gz", member) => Ok(control_from_tar(flate2::read::GzDecoder::new(member))?),
+            ("control.tar", member) => Ok(control_from_tar(member)?),
+            _ => {
+                let out = cmd::app("dpkg-deb")
+                    .ok_or_else(|| Error::ToolNotFound("dpkg-deb".to_string()))?
+                    .arg("--field")
+                    .arg(deb)
+                    .output()?;
+                Ok(String::from_utf8_lossy(&out.stdout).into_owned())
+            }
+        }
+    }
+
+    /// The package's files as an uncompressed tarball, gzip and plain data
+    /// archives are read directly and `dpkg-deb` is used for the rest
+    fn data_tar(deb: &Path) -> Result<Vec<u8>, Error> {
+        let data = fs::read(deb)?;
+        match member(&data, "data.tar")? {
+            ("data.tar.gz", member) => {
+                let mut tar = Vec::new();
+                flate2::read::GzDecoder::new(member).read_to_end(&mut tar)?;
+                Ok(tar)
+            }
+            ("data.tar", member) => Ok(member.to_vec()),
+            _ => {
+                let out = cmd::app("dpkg-deb")
+                    .ok_or_else(|| Error::ToolNotFound("dpkg-deb".to_string()))?
+                    .arg("--fsys-tarfile")
+                    .arg(deb)
+                    .output()?;
+                Ok(out.stdout)
+            }
+        }
+    }
+
+    const APPLICATIONS: &str = "usr/share/applications/";
+
+    /// What the package's own desktop file says, paths are relative to the
+    /// package's root like `usr/share/applications/app.desktop`
+    #[derive(Debug, PartialEq)]
+    pub struct DesktopInfo {
+        pub desktop_file: String,
+        pub name: Option<String>,
+        pub categories: Vec<String>,
+        pub icon: Option<String>,
+    }
+
+    /// Reads the package's desktop file, the one named after the package if
+    /// there are several
+    pub fn desktop_info(deb: &Path) -> Result<Option<DesktopInfo>, Error> {
+        let package = control(deb).ok().and_then(|control| field(&control, "Package")).unwrap_or_default();
+        let tar = data_tar(deb)?;
+
+        let mut desktops = Vec::new();
+        let mut files = Vec::new();
+        for entry in tar::Archive::new(tar.as_slice()).entries()? {
+            let mut entry = entry?;
+            let path = entry.path()?.to_string_lossy().trim_start_matches("./").to_string();
+            if path.starts_with(APPLICATIONS) && path.ends_with(".desktop") {
+                let mut contents = String::new();
+                entry.read_to_string(&mut contents)?;
+                desktops.push((path, contents));
+            } else {
+                files.push(path);
+            }
+        }
+        if desktops.is_empty() {
+            return Ok(None);
+        }
+
+        desktops.sort();
+        let primary = desktops.iter().position(|(path, _)| Path::new(path).file_stem().is_some_and(|s| *s == *package)).unwrap_or(0);
+        let (desktop_file, contents) = desktops.swap_remove(primary);
+        let value = |key| desktop_entry::get_value(&contents, "Desktop Entry", key);
+
+        Ok(Some(DesktopInfo {
+            name: value("Name").map(str::to_string),
+            categories: value("Categories")
+                .map(|c| c.split(';').filter(|c| !c.is_empty()).map(str::to_string).collect())
+                .unwrap_or_default(),
+            icon: value("Icon").and_then(|icon| find_icon(&files, icon)),
+            desktop_file,
+        }))
+    }
+
+    /// The executable the package is about, relative to the package's root
+    /// like `usr/bin/app`, with the package's icon of the same name
+    #[derive(Debug, PartialEq)]
+    pub struct MainBinary {
+        pub path: String,
+        pub icon: Option<String>,
+    }
+
+    /// Finds the package's main binary: `usr/bin/<package>` or else the only
+    /// file in `usr/bin`
+    pub fn main_binary(deb: &Path) -> Result<Option<MainBinary>, Error> {
+        let package = control(deb).ok().and_then(|control| field(&control, "Package")).unwrap_or_default();
+        let tar = data_tar(deb)?;
+
+        let mut files = Vec::new();
+        for entry in tar::Archive::new(tar.as_slice()).entries()? {
+            let entry = entry?;
+            if !entry.header().entry_type().is_dir() {
+                files.push(entry.path()?.to_string_lossy().trim_start_matches("./").to_string());
+            }
+        }
+
+        let binaries: Vec<&String> = files
+            .iter()
+            .filter(|f| f.strip_prefix("usr/bin/").is_some_and(|name| !name.is_empty() && !name.contains('/')))
+            .collect();
+        let path = match binaries.iter().find(|f| f.strip_prefix("usr/bin/") == Some(package.as_str())) {
+            Some(path) => (*path).clone(),
+            None if binaries.len() == 1 => binaries[0].clone(),
+            None => return Ok(None),
+        };
+
+        let name = path.trim_start_matches("usr/bin/");
+        Ok(Some(MainBinary { icon: find_icon(&files, name), path }))
+    }
+
+    /// The package's best icon called `name`: a scalable one, then the
+    /// biggest one in hicolor and then one in pixmaps
+    fn find_icon(files: &[String], name: &str) -> Option<String> {
+        if let Some(absolute) = name.strip_prefix('/') {
+            return files.iter().find(|f| *f == absolute).cloned();
+        }
+
+        files
+            .iter()
+            .filter_map(|file| {
+                let path = Path::new(file);
+                let is_image = ["png", "svg"].iter().any(|ext| path.extension().is_some_and(|e| e == *ext));
+                if !is_image || path.file_stem()? != name {
+                    return None;
+                }
+
+                let rank = if let Some(themed) = file.strip_prefix("usr/share/icons/hicolor/") {
+                    let size = themed.split('/').next()?;
+                    if size == "scalable" { u32::MAX } else { size.split_once('x')?.0.parse().ok()? }
+                } else if file.starts_with("usr/share/pixmaps/") {
+                    0
+                } else {
+                    return None;
+                };
+                Some((rank, file))
+            })
+            .max_by_key(|(rank, _)| *rank)
+            .map(|(_, file)| file.clone())
+    }
+
+    /// The value of `field` in a control file, continuation lines included
+    fn field(control: &str, field: &str) -> Option<String> {
+        let mut lines = control.lines().skip_while(|l| {
+            l.split_once(':').is_none_or(|(key, _)| !key.eq_ignore_ascii_case(field))
+        });
+        let first = lines.next()?.split_once(':')?.1.trim();
+        let rest = lines.take_while(|l| l.starts_with([' ', '\t'])).map(str::trim);
+
+        Some(std::iter::once(first).chain(rest).collect::<Vec<_>>().join(" "))
+    }
+
+    /// The package's version, from its control file or else from its
+    /// `name_version_arch.deb` file name
+    pub fn version(deb: &Path) -> Option<String> {
+        control(deb)
+            .ok()
+            .and_then(|control| field(&control, "Version"))
+            .filter(|v| !v.is_empty())
+            .or_else(|| {
+                let stem = deb.file_stem()?.to_string_lossy().into_owned();
+                stem.split('_').nth(1).filter(|v| !v.is_empty()).map(str::to_string)
+            })
+    }
+
+    /// Names of the packages in `Depends`, for alternatives only the first one
+    /// is taken and base system packages are left out
+    pub fn depends(control: &str) -> Vec<String> {
+        let Some(depends) = field(control, "Depends") else {
+            return Vec::new();
+        };
+
+        depends
+            .split(',')
+            .filter_map(|dep| {
+                let first = dep.split('|').next()?;
+                let name = first.split(['(', ' ']).find(|s| !s.is_empty())?;
+                Some(name.split(':').next().unwrap_or(name).trim().to_string())
+            })
+            .filter(|name| !name.is_empty() && !BASE_PACKAGES.contains(&name.as_str()))
+            .collect()
+    }
+}
+
+const ICON_SIZES: [u32; 6] = [16, 32, 48, 64, 128, 256];
+
+/// Writes a copy of `icon` for each size inside the AppDir's hicolor theme,
+/// the sizes are generated in parallel
+fn generate_icon_sizes(icon: &Path, appdir: &Path, sizes: &[u32]) -> Result<Vec<PathBuf>, Error> {
+    use image::ImageReader;
+
+    let img = ImageReader::open(icon)?.decode()?;
+    let name = icon.file_stem().unwrap_or_default().to_string_lossy();
+    let hicolor = appdir.join("usr").join("share").join("icons").join("hicolor");
+
+    sizes
+        .par_iter()
+        .map(|&size| -> Result<PathBuf, Error> {
+            let dir = hicolor.join(format!("{size}x{size}")).join("apps");
+            fs::create_dir_all(&dir)?;
+
+            let path = dir.join(format!("{name}.png"));
+            resize(&img, size, size, image::imageops::FilterType::Lanczos3).save(&path)?;
+            Ok(path)
+        })
+        .collect()
+}
+
+trait ExtUtils {
+    fn get_ext(&self) -> &str;
+    fn is_ext(&self, ext: &str) -> bool;
+}
+
+impl ExtUtils for &Path {
+    fn get_ext(&self) -> &str {
+        self.extension().unwrap_or_default().to_str().unwrap_or("")
+    }
+    fn is_ext(&self, ext: &str) -> bool {
+        self.get_ext().eq_ignore_ascii_case(ext)
+    }
+}
+
+impl ExtUtils for PathBuf {
+    fn get_ext(&self) -> &str {
+        self.extension().unwrap_or_default().to_str().unwrap_or("")
+    }
+    fn is_ext(&self, ext: &str) -> bool {
+        self.get_ext().eq_ignore_ascii_case(ext)
+    }
+}
+
+
+/// Downloads `url` inside `dir`, keeping the URL's file name
+#[cfg(feature = "net")]
+fn download_into(url: &str, dir: &Path, timeout: Option<Duration>) -> Result<(), Error> {
+    download_file(url, &dir.join(http::file_name(url)), timeout)
+}
+
+/// Downloads `url` as `file` while showing the progress
+#[cfg(feature = "net")]
+fn download_file(url: &str, file: &Path, timeout: Option<Duration>) -> Result<(), Error> {
+    let mut reporter = progress::Reporter::new(&http::file_name(url));
+    http::download(url, file, timeout, |done, total| reporter.update(done, total))?;
+    reporter.finish();
+    Ok(())
+}
+
+#[cfg(not(feature = "net"))]
+fn download_into(url: &str, dir: &Path, timeout: Option<Duration>) -> Result<(), Error> {
+    // curl and wget are given the timeout themselves
+    cmd::run_with(&mut cmd::download_to(url, dir, timeout)?, Duration::MAX)
+}
+
+#[cfg(not(feature = "net"))]
+fn download_file(url: &str, file: &Path, timeout: Option<Duration>) -> Result<(), Error> {
+    cmd::download_as(url, file, timeout)
+}
+
+fn download_to_temp(tmp_path: &Path, url: &str, timeout: Option<Duration>) -> Result<String, Error> {
+    download_into(url, tmp_path, timeout)?;
+
+    let downloaded = cache::first_file(tmp_path).ok_or_else(|| Error::DownloadFailed(url.to_string()))?;
+    Ok(downloaded.to_string_lossy().into_owned())
+}
+
+/// Stops offline builds before `what` is fetched
+fn online(config: &Config, what: &str) -> Result<(), Error> {
+    if config.offline {
+        Err(Error::Offline(what.to_string()))
+    } else {
+        Ok(())
+    }
+}
+
+/// Downloads `url`, going through the cache unless it has been disabled
+fn download(config: &Config, url: &str) -> Result<String, Error> {
+    let checksum = config.checksum.as_deref();
+    let cache_dir = cache::dir().filter(|_| !config.no_cache);
+
+    let file = if let Some(cache_dir) = cache_dir {
+        cache::fetch(&cache_dir, url, checksum, |dir| {
+            online(config, url)?;
+            download_into(url, dir, config.download_timeout)
+        })?
+    } else {
+        online(config, url)?;
+        let tmp_path = temp::try_create(&format!("download-{}", cache::key(url)))?;
+        let file = PathBuf::from(download_to_temp(&tmp_path, url, config.download_timeout)?);
+        cache::verify(&file, checksum)?;
+        file
+    };
+
+    Ok(file.to_string_lossy().into_owned())
+}
+
+#[cfg_attr(not(feature = "net"), allow(dead_code))]
+mod http {
+    use std::io::{self, Read, Write};
+    #[cfg(feature = "net")]
+    use std::{
+        fs::{self, File},
+        path::Path,
+        time::Duration,
+    };
+
+    #[cfg(feature = "net")]
+    use crate::Error;
+
+    /// The name a download is saved as, the last segment of the URL's path
+    pub fn file_name(url: &str) -> String {
+        let path = url.split(['?', '#']).next().unwrap_or(url);
+        path.trim_end_matches('/')
+            .rsplit('/')
+            .next()
+            .filter(|name| !name.is_empty())
+            .unwrap_or("download")
+            .to_string()
+    }
+
+    /// Copies everything from `reader`, telling `progress` how many bytes
+    /// have been copied so far and how many are expected
+    pub fn copy_with_progress<R, W, F>(mut reader: R, mut writer: W, total: Option<u64>, mut progress: F) -> io::Result<u64>
+    where
+        R: Read,
+        W: Write,
+        F: FnMut(u64, Option<u64>),
+    {
+        let mut buf = [0; 64 * 1024];
+        let mut done = 0;
+        loop {
+            let read = match reader.read(&mut buf) {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+
+            writer.write_all(&buf[..read])?;
+            done += read as u64;
+            progress(done, total);
+        }
+
+        writer.flush()?;
+        Ok(done)
+    }
+
+    const MAX_REDIRECTS: u32 = 10;
+
+    /// Downloads `url` as `file` following redirects, HTTPS is handled with
+    /// rustls so no system library is needed
+    #[cfg(feature = "net")]
+    pub fn download<F>(url: &str, file: &Path, timeout: Option<Duration>, progress: F) -> Result<(), Error>
+    where
+        F: FnMut(u64, Option<u64>),
+    {
+        let failed = |reason: String| Error::Http { url: url.to_string(), reason };
+
+        let mut agent = ureq::AgentBuilder::new().redirects(MAX_REDIRECTS);
+        if let Some(timeout) = timeout {
+            agent = agent.timeout(timeout);
+        }
+
+        let response = agent.build().get(url).call().map_err(|e| failed(e.to_string()))?;
+        let total = response.header("Content-Length").and_then(|l| l.parse().ok());
+
+        // Write to a separate file so an interrupted download is never taken
+        // as a complete one
+        let partial = file.with_extension("part");
+        match copy_with_progress(response.into_reader(), File::create(&partial)?, total, progress) {
+            Ok(_) => Ok(fs::rename(&partial, file)?),
+            Err(e) => {
+                let _ = fs::remove_file(&partial);
+                Err(failed(e.to_string()))
+            }
+        }
+    }
+}
+
+/// Shared libraries of bare executables, found with `ldd`
+mod libs {
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+    };
+
+    use itertools::Itertools;
+
+    use crate::{cmd, cmd::RunExt, warn, Config, Error};
+
+    /// glibc and the loader always come from the host, mixing them with
+    /// bundled copies breaks on ABI mismatches
+    const SYSTEM_LIBS: &[&str] = &[
+        "linux-vdso.so",
+        "ld-linux",
+        "libc.so",
+        "libm.so",
+        "libmvec.so",
+        "libdl.so",
+        "libpthread.so",
+        "librt.so",
+        "libresolv.so",
+        "libutil.so",
+        "libnsl.so",
+        "libanl.so",
+    ];
+
+    fn is_system(name: &str) -> bool {
+        SYSTEM_LIBS.iter().any(|lib| name.starts_with(lib))
+    }
+
+    /// The libraries in `ldd`'s output that have to be bundled, missing ones
+    /// can't be and are warned about
+    pub fn parse_ldd(config: &Config, output: &str) -> Result<Vec<PathBuf>, Error> {
+        let mut libs = Vec::new();
+        for (name, rest) in output.lines().filter_map(|line| line.trim().split_once(" => ")) {
+            let path = rest.split(" (").next().unwrap_or_default().trim();
+            if path == "not found" {
+                warn(config, &format!("'{name}' couldn't be found, it won't be bundled"))?;
+            } else if path.starts_with('/') && !is_system(name) {
+                libs.push(PathBuf::from(path));
+            }
+        }
+        Ok(libs)
+    }
+
+    pub fn dependencies(config: &Config, executable: &Path) -> Result<Vec<PathBuf>, Error> {
+        let mut ldd = cmd::app("ldd").ok_or_else(|| Error::ToolNotFound("ldd".to_string()))?;
+        let out = ldd.arg(executable).output()?;
+
+        // Scripts and static executables have nothing to bundle
+        if !out.status.success() {
+            return Ok(Vec::new());
+        }
+        parse_ldd(config, &String::from_utf8_lossy(&out.stdout))
+    }
+
+    /// Loader folders every distro has, interpreters anywhere else only exist
+    /// on the build host
+    const LOADER_DIRS: [&str; 4] = ["/lib/", "/lib64/", "/usr/lib/", "/usr/lib64/"];
+
+    /// The loader every glibc system has for this architecture
+    fn system_interpreter() -> Option<&'static str> {
+        match std::env::consts::ARCH {
+            "x86_64" => Some("/lib64/ld-linux-x86-64.so.2"),
+            "aarch64" => Some("/lib/ld-linux-aarch64.so.1"),
+            _ => None,
+        }
+    }
+
+    /// Where an ELF looks for its loader and its libraries
+    #[derive(Debug, Default, PartialEq)]
+    pub struct Paths {
+        pub interpreter: Option<String>,
+        pub rpath: Vec<String>,
+    }
+
+    impl Paths {
+        /// `patchelf` gets a `--print-*` flag and returns what was printed,
+        /// `None` if it failed like it does for static executables
+        pub fn read<F: Fn(&str) -> Option<String>>(patchelf: F) -> Self {
+            Paths {
+                interpreter: patchelf("--print-interpreter").map(|i| i.trim().to_string()).filter(|i| !i.is_empty()),
+                rpath: patchelf("--print-rpath")
+                    .map(|r| r.trim().split(':').filter(|p| !p.is_empty()).map(str::to_string).collect())
+                    .unwrap_or_default(),
+            }
+        }
+
+        /// The interpreter, unless it's in one of the usual loader folders
+        pub fn host_interpreter(&self) -> Option<&str> {
+            self.interpreter
+                .as_deref()
+                .filter(|i| !LOADER_DIRS.iter().any(|dir| i.strip_prefix(dir).is_some_and(|name| !name.contains('/'))))
+        }
+
+        /// Absolute RPATH entries, unlike `$ORIGIN` ones they don't move with
+        /// the AppImage
+        pub fn host_rpath(&self) -> Vec<&str> {
+            self.rpath.iter().map(String::as_str).filter(|p| p.starts_with('/')).collect()
+        }
+
+        /// The `$ORIGIN` entries, then the AppDir's `usr/lib` relative to the
+        /// folder of `executable`, which is relative to the AppDir
+        pub fn relative_rpath(&self, executable: &Path) -> String {
+            let depth = executable.parent().map_or(0, |dir| dir.components().count());
+            let lib = format!("$ORIGIN{}/usr/lib", "/..".repeat(depth));
+            self.rpath
+                .iter()
+                .filter(|p| !p.starts_with('/'))
+                .cloned()
+                .chain(std::iter::once(lib))
+                .unique()
+                .join(":")
+        }
+    }
+
+    /// `patchelf`'s output for `flag` on `executable`
+    pub fn print(executable: &Path, flag: &str) -> Option<String> {
+        let out = cmd::app("patchelf")?.arg(flag).arg(executable).output().ok()?;
+        out.status.success().then(|| String::from_utf8_lossy(&out.stdout).into_owned())
+    }
+
+    /// Rewrites what `Paths` finds host-specific, the interpreter can't be
+    /// relative so the system's loader is used. The executable is changed in
+    /// place, it has to be inside a staged AppDir
+    pub fn patch(config: &Config, appdir: &Path, executable: &Path, paths: &Paths) -> Result<(), Error> {
+        let mut patchelf = cmd::app("patchelf").ok_or_else(|| Error::ToolNotFound("patchelf".to_string()))?;
+        if paths.host_interpreter().is_some() {
+            let interpreter = system_interpreter().ok_or_else(|| Error::ArchitectureNotSupported(std::env::consts::ARCH.to_string()))?;
+            patchelf.arg("--set-interpreter").arg(interpreter);
+        }
+        if !paths.host_rpath().is_empty() {
+            let relative = executable.strip_prefix(appdir).unwrap_or(executable);
+            patchelf.arg("--set-rpath").arg(paths.relative_rpath(relative));
+        }
+        (&mut patchelf.arg(executable)).run(config)
+    }
+
+    /// Copies `libs` into the AppDir's `usr/lib`, symlinks are followed
+    pub fn bundle(appdir: &Path, libs: &[PathBuf]) -> Result<(), Error> {
+        let lib_dir = appdir.join("usr").join("lib");
+        fs::create_dir_all(&lib_dir)?;
+        for lib in libs {
+            fs::copy(lib, lib_dir.join(lib.file_name().unwrap_or_default()))?;
+        }
+        Ok(())
+    }
+}
+
+/// Trees exported by `flatpak build-finish`, with the app under `files` (its
+/// `/app`) and what it shows the desktop under `export`
+mod flatpak {
+    use std::{
+        fs,
+        os::unix::fs::symlink,
+        path::{Path, PathBuf},
+    };
+
+    use crate::{apprun, desktop_entry, existing_apprun, mark_executable, resolve_icon, Error};
+
+    const APPLICATIONS: &str = "export/share/applications";
+
+    pub fn is_export(dir: &Path) -> bool {
+        dir.join("metadata").is_file() && dir.join("files").join("bin").is_dir() && dir.join(APPLICATIONS).is_dir()
+    }
+
+    /// What the export's `metadata` and desktop file say
+    #[derive(Debug, PartialEq)]
+    pub struct Export {
+        pub id: String,
+        pub command: String,
+        pub desktop_file: PathBuf,
+        pub name: Option<String>,
+        pub icon: Option<String>,
+    }
+
+    impl Export {
+        /// The command relative to the export, `/app` is `files`
+        pub fn target(&self) -> PathBuf {
+            match self.command.strip_prefix("/app/") {
+                Some(inside) => Path::new("files").join(inside),
+                None => Path::new("files").join("bin").join(&self.command),
+            }
+        }
+    }
+
+    /// Reads the export's metadata and its desktop file, the one named after
+    /// the app if there are several
+    pub fn read(dir: &Path) -> Result<Export, Error> {
+        let metadata = fs::read_to_string(dir.join("metadata"))?;
+        let value = |key| desktop_entry::get_value(&metadata, "Application", key).filter(|v| !v.is_empty());
+        let id = value("name").ok_or_else(|| Error::FlatpakExport("has no app name in its metadata".to_string()))?.to_string();
+        let command = value("command").ok_or_else(|| Error::FlatpakExport("has no command in its metadata".to_string()))?.to_string();
+
+        let mut desktops: Vec<_> = fs::read_dir(dir.join(APPLICATIONS))?
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|e| e == "desktop"))
+            .collect();
+        if desktops.is_empty() {
+            return Err(Error::FlatpakExport("has no desktop file".to_string()));
+        }
+        desktops.sort();
+        let primary = desktops.iter().position(|p| p.file_stem().is_some_and(|s| *s == *id)).unwrap_or(0);
+        let desktop_file = desktops.swap_remove(primary);
+
+        let contents = fs::read_to_string(&desktop_file)?;
+        let value = |key| desktop_entry::get_value(&contents, "Desktop Entry", key).map(str::to_string);
+        Ok(Export { name: value("Name"), icon: value("Icon"), id, command, desktop_file })
+    }
+
+    /// Turns the export into an AppDir: `usr` points to `files` so its
+    /// metainfo and icons are where appimagetool looks, and the desktop file,
+    /// its icon and an AppRun running the command with `env` go in the root.
+    /// `dir` is changed, pass a staged copy of the user's export
+    pub fn install(dir: &Path, export: &Export, env: &[(String, String)]) -> Result<(), Error> {
+        let usr = dir.join("usr");
+        if fs::symlink_metadata(&usr).is_err() {
+            symlink("files", &usr)?;
+        }
+
+        fs::copy(&export.desktop_file, dir.join(format!("{}.desktop", export.id)))?;
+
+        if let Some(icon) = &export.icon {
+            let found = resolve_icon(dir, icon).ok_or_else(|| Error::FlatpakExport(format!("has no '{icon}' icon")))?;
+            let name = Path::new(icon).file_name().unwrap_or_default().to_string_lossy();
+            let ext = found.extension().unwrap_or_default().to_string_lossy();
+            let in_root = if name.ends_with(&format!(".{ext}")) { name.into_owned() } else { format!("{name}.{ext}") };
+            fs::copy(&found, dir.join(in_root))?;
+        }
+
+        if existing_apprun(dir).is_some() {
+            eprintln!("Keeping the AppRun shipped with the app");
+        } else {
+            let apprun_path = dir.join("AppRun");
+            fs::write(&apprun_path, apprun(&export.target(), true, env))?;
+            mark_executable(&apprun_path)?;
+        }
+        Ok(())
+    }
+}
+
+mod github {
+    // Without `net` the releases can't be fetched, picking assets is unused
+    #![cfg_attr(not(feature = "net"), allow(dead_code))]
+
+    use std::time::Duration;
+
+    use serde::Deserialize;
+
+    use crate::Error;
+
+    pub const PREFIX: &str = "gh:";
+
+    /// Raises the API's rate limit when set
+    pub const TOKEN_VAR: &str = "GITHUB_TOKEN";
+
+    const API: &str = "https://api.github.com";
+
+    #[derive(Debug, Deserialize)]
+    pub struct Release {
+        pub tag_name: String,
+        pub assets: Vec<Asset>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    pub struct Asset {
+        pub name: String,
+        pub browser_download_url: String,
+    }
+
+    /// Extensions of the assets that can be built, from most to least
+    /// preferred
+    const KINDS: [&[&str]; 3] = [&[".appimage"], &[".tar.gz", ".tgz", ".tar.xz", ".tar.bz2", ".zip"], &[".deb"]];
+
+    /// Every architecture name assets tend to use
+    const ARCHES: [&str; 10] = ["x86_64", "amd64", "x64", "aarch64", "arm64", "armhf", "armv7", "i386", "i686", "riscv64"];
+
+    /// Words of assets for other systems
+    const OTHER_SYSTEMS: [&str; 7] = ["windows", "win32", "win64", "darwin", "macos", "osx", "mac-"];
+
+    fn arch_names(arch: &str) -> &'static [&'static str] {
+        match arch {
+            "x86_64" => &["x86_64", "amd64", "x64"],
+            "aarch64" => &["aarch64", "arm64"],
+            _ => &[],
+        }
+    }
+
+    pub fn release_url(repo: &str, tag: Option<&str>) -> String {
+        match tag {
+            Some(tag) => format!("{API}/repos/{repo}/releases/tags/{tag}"),
+            None => format!("{API}/repos/{repo}/releases/latest"),
+        }
+    }
+
+    /// The best asset for `arch`: an AppImage, then a tarball and then a deb,
+    /// of each one those naming the architecture go before those naming none
+    pub fn pick_asset<'a>(release: &'a Release, arch: &str) -> Option<&'a Asset> {
+        let rank = |asset: &Asset| {
+            let name = asset.name.to_lowercase();
+            let kind = KINDS.iter().position(|exts| exts.iter().any(|ext| name.ends_with(ext)))?;
+            if OTHER_SYSTEMS.iter().any(|s| name.contains(s)) {
+                None
+            } else if arch_names(arch).iter().any(|a| name.contains(a)) {
+                Some((kind, 0))
+            } else if ARCHES.iter().any(|a| name.contains(a)) {
+                None
+            } else {
+                Some((kind, 1))
+            }
+        };
+
+        release
+            .assets
+            .iter()
+            .filter_map(|asset| rank(asset).map(|r| (r, asset)))
+            .min_by_key(|(r, _)| *r)
+            .map(|(_, asset)| asset)
+    }
+
+    fn check_repo(repo: &str) -> Result<(), Error> {
+        let valid = repo.split('/').filter(|part| !part.is_empty()).count() == 2 && repo.matches('/').count() == 1;
+        if valid {
+            Ok(())
+        } else {
+            Err(Error::InvalidGitHubRepo(repo.to_string()))
+        }
+    }
+
+    /// Asks GitHub for a release of `repo` and returns the URL of its best
+    /// asset
+    #[cfg(feature = "net")]
+    pub fn asset_url(repo: &str, tag: Option<&str>, timeout: Option<Duration>) -> Result<String, Error> {
+        check_repo(repo)?;
+
+        let url = release_url(repo, tag);
+        let token = std::env::var(TOKEN_VAR).ok().filter(|t| !t.is_empty());
+        let body = get(&url, token.as_deref(), timeout)?;
+        let release: Release = serde_json::from_str(&body).map_err(|e| Error::Http { url: url.clone(), reason: e.to_string() })?;
+
+        pick_asset(&release, std::env::consts::ARCH)
+            .map(|asset| asset.browser_download_url.clone())
+            .ok_or_else(|| Error::NoReleaseAsset { repo: repo.to_string(), tag: release.tag_name.clone() })
+    }
+
+    #[cfg(feature = "net")]
+    fn get(url: &str, token: Option<&str>, timeout: Option<Duration>) -> Result<String, Error> {
+        let failed = |reason: String| Error::Http { url: url.to_string(), reason };
+
+        let mut agent = ureq::AgentBuilder::new();
+        if let Some(timeout) = timeout {
+            agent = agent.timeout(timeout);
+        }
+
+        let mut request = agent.build().get(url).set("Accept", "application/vnd.github+json").set("User-Agent", "to_appimage");
+        if let Some(token) = token {
+            request = request.set("Authorization", &format!("Bearer {token}"));
+        }
+
+        match request.call() {
+            Ok(response) => response.into_string().map_err(|e| failed(e.to_string())),
+            Err(ureq::Error::Status(403 | 429, _)) => Err(Error::GitHubRateLimited),
+            Err(e) => Err(failed(e.to_string())),
+        }
+    }
+
+    #[cfg(not(feature = "net"))]
+    pub fn asset_url(repo: &str, _tag: Option<&str>, _timeout: Option<Duration>) -> Result<String, Error> {
+        check_repo(repo)?;
+        Err(Error::GitHubWithoutNet(repo.to_string()))
+    }
+}
+
+mod progress {
+    #[cfg(feature = "net")]
+    use std::io::IsTerminal;
+    use std::time::{Duration, Instant};
+
+    /// Times the phases of a build, which are only logged when enabled
+    pub struct Steps {
+        enabled: bool,
+        current: Option<(&'static str, Instant)>,
+        done: Vec<(&'static str, Duration)>,
+    }
+
+    impl Steps {
+        pub fn new(enabled: bool) -> Self {
+            Self { enabled, current: None, done: Vec::new() }
+        }
+
+        /// Ends the current step and starts `name`
+        pub fn start(&mut self, name: &'static str) {
+            self.finish();
+            if self.enabled {
+                eprintln!("==> {name}");
+            }
+            self.current = Some((name, Instant::now()));
+        }
+
+        pub fn finish(&mut self) {
+            if let Some((name, start)) = self.current.take() {
+                let elapsed = start.elapsed();
+                if self.enabled {
+                    eprintln!("<== {name} ({elapsed:.1?})");
+                }
+                self.done.push((name, elapsed));
+            }
+        }
+
+        // Only the tests look at the raw timings, builds print the summary
+        #[cfg(test)]
+        pub fn timings(&self) -> &[(&'static str, Duration)] {
+            &self.done
+        }
+
+        /// A line per finished step and their total
+        pub fn summary(&self) -> String {
+            let width = self.done.iter().map(|(name, _)| name.len()).max().unwrap_or(0).max("total".len());
+            let total: Duration = self.done.iter().map(|(_, elapsed)| *elapsed).sum();
+            self.done
+                .iter()
+                .chain(std::iter::once(&("total", total)))
+                .map(|(name, elapsed)| format!("{name:<width$}  {elapsed:>8.1?}\n"))
+                .collect()
+        }
+    }
+
+    /// Shows how far a download is, on terminals as a line that keeps being
+    /// rewritten and otherwise as a line every 10%. Only the built-in client
+    /// reports progress
+    #[cfg(feature = "net")]
+    pub struct Reporter {
+        label: String,
+        terminal: bool,
+        last: Option<u64>,
+    }
+
+    #[cfg(feature = "net")]
+    impl Reporter {
+        pub fn new(label: &str) -> Self {
+            Self {
+                label: label.to_string(),
+                terminal: std::io::stderr().is_terminal(),
+                last: None,
+            }
+        }
+
+        pub fn update(&mut self, done: u64, total: Option<u64>) {
+            // Without a Content-Length only the downloaded size can be shown
+            let Some(total) = total.filter(|t| *t > 0) else {
+                if self.terminal {
+                    eprint!("\r{}: {} KiB", self.label, done / 1024);
+                }
+                return;
+            };
+
+            let percent = (done * 100 / total).min(100);
+            if self.terminal {
+                if self.last != Some(percent) {
+                    eprint!("\r{}: {percent}%", self.label);
+                }
+            } else if self.last.is_none_or(|last| last / 10 < percent / 10) {
+                eprintln!("{}: {percent}%", self.label);
+            }
+            self.last = Some(percent);
+        }
+
+        pub fn finish(&mut self) {
+            if self.terminal {
+                eprintln!();
+            }
+        }
+    }
+}
+
+mod cache {
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+    };
+
+    use sha2::{Digest, Sha256};
+
+    use crate::Error;
+
+    /// Where downloads are kept between runs
+    pub fn dir() -> Option<PathBuf> {
+        directories::BaseDirs::new().map(|d| d.cache_dir().join("to_appimage").join("downloads"))
+    }
+
+    pub fn key(url: &str) -> String {
+        format!("{:x}", Sha256::digest(url.as_bytes()))
+    }
+
+    pub fn sha256(path: &Path) -> Result<String, Error> {
+        // AppImages can be big, so don't read them whole
+        let mut hasher = Sha256::new();
+        std::io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+
+    pub fn first_file(dir: &Path) -> Option<PathBuf> {
+        fs::read_dir(dir).ok()?.flatten().map(|e| e.path()).find(|p| p.is_file())
+    }
+
+    pub fn verify(file: &Path, checksum: Option<&str>) -> Result<(), Error> {
+        let Some(expected) = checksum else {
+            return Ok(());
+        };
+
+        let actual = sha256(file)?;
+        if actual.eq_ignore_ascii_case(expected) {
+            Ok(())
+        } else {
+            Err(Error::ChecksumMismatch { expected: expected.to_string(), actual })
+        }
+    }
+
+    /// Returns the cached file for `url`, calling `download` with an empty
+    /// directory to fill when there's no valid one
+    pub fn fetch<F>(cache_dir: &Path, url: &str, checksum: Option<&str>, download: F) -> Result<PathBuf, Error>
+    where
+        F: FnOnce(&Path) -> Result<(), Error>,
+    {
+        let entry_dir = cache_dir.join(key(url));
+        if let Some(file) = first_file(&entry_dir) {
+            if verify(&file, checksum).is_ok() {
+                return Ok(file);
+            }
+        }
+
+        // Download into a separate directory so an interrupted download is
+        // never taken as a cached one
+        let partial_dir = entry_dir.with_extension("part");
+        for dir in [&entry_dir, &partial_dir] {
+            if dir.exists() {
+                fs::remove_dir_all(dir)?;
+            }
+        }
+        fs::create_dir_all(&partial_dir)?;
+
+        download(&partial_dir)?;
+        let file = first_file(&partial_dir).ok_or_else(|| Error::DownloadFailed(url.to_string()))?;
+        verify(&file, checksum)?;
+
+        fs::rename(&partial_dir, &entry_dir)?;
+        let name = file.file_name().ok_or_else(|| Error::DownloadFailed(url.to_string()))?;
+        Ok(entry_dir.join(name))
+    }
+}
+
+enum PkgType {
+    Deb(PathBuf),
+    Yaml(PathBuf),
+    Flatpak(PathBuf),
+    Other(PathBuf),
+}
+
+mod temp {
+    use std::{
+        cell::Cell,
+        fs, io,
+        path::{Path, PathBuf},
+        sync::atomic::{AtomicU64, Ordering},
+    };
+
+    static NEXT_ROOT: AtomicU64 = AtomicU64::new(0);
+
+    thread_local! {
+        // Builds running at once, like tests, can't clean each other's files
+        static ROOT: Cell<u64> = Cell::new(NEXT_ROOT.fetch_add(1, Ordering::Relaxed));
+    }
+
+    fn get_common() -> PathBuf {
+        Path::new("/tmp/to_appimage").into()
+    }
+    fn get_base() -> PathBuf {
+        get_common().join(format!("{}-{}", std::process::id(), ROOT.with(Cell::get)))
+    }
+
+    /// Gives what follows on this thread a folder of its own, called at the
+    /// start of each build
+    pub fn start() {
+        ROOT.with(|root| root.set(NEXT_ROOT.fetch_add(1, Ordering::Relaxed)));
+    }
+
+    pub fn get(identifier: &str) -> PathBuf {
+        get_base().join(identifier)
+    }
+
+    /// Whether `path` is something the build staged, like an extracted
+    /// archive, rather than a file of the user's
+    pub fn contains(path: &Path) -> bool {
+        path.starts_with(get_base())
+    }
+
+    pub fn try_create(identifier: &str) -> io::Result<PathBuf> {
+        let tmp_path = get(identifier);
+        if !tmp_path.exists() {
+            fs::create_dir_all(&tmp_path)?;
+        }
+        Ok(tmp_path)
+    }
+
+    pub fn clean_everything() -> io::Result<()> {
+        let tmp_path = get_base();
+        if tmp_path.exists() {
+            fs::remove_dir_all(&tmp_path)?;
+        }
+
+        // Erase /tmp/to_appimage if it's empty, other builds may still be
+        // using it
+        let _ = fs::remove_dir(get_common());
+        Ok(())
+    }
+
+    /// Whether `pid` is running, `/proc` tells without signalling it
+    pub fn is_alive(pid: u32) -> bool {
+        Path::new("/proc").join(pid.to_string()).exists()
+    }
+
+    /// Symlinks aren't followed, what they point to isn't freed
+    fn size(path: &Path) -> io::Result<u64> {
+        let metadata = fs::symlink_metadata(path)?;
+        if !metadata.is_dir() {
+            return Ok(metadata.len());
+        }
+        fs::read_dir(path)?.try_fold(0, |total, entry| Ok(total + size(&entry?.path())?))
+    }
+
+    /// Removes the folders in `common` of runs that are gone, according to
+    /// `alive`, and returns how many bytes were freed; folders not named
+    /// after a pid, like `<pid>-<build>`, and this run's are left alone
+    pub fn remove_stale<F: Fn(u32) -> bool>(common: &Path, alive: F) -> io::Result<u64> {
+        let Ok(entries) = fs::read_dir(common) else {
+            return Ok(0);
+        };
+
+        let mut freed = 0;
+        for entry in entries.flatten() {
+            let Some(pid) = entry.file_name().to_str().and_then(|name| name.split('-').next()?.parse::<u32>().ok()) else {
+                continue;
+            };
+            if pid == std::process::id() || alive(pid) {
+                continue;
+            }
+
+            let path = entry.path();
+            freed += size(&path)?;
+            fs::remove_dir_all(&path)?;
+        }
+        Ok(freed)
+    }
+
+    pub fn clean_stale() -> io::Result<u64> {
+        remove_stale(&get_common(), is_alive)
+    }
+}
+
+impl PkgType {
+    fn guess(input: &str, config: &Config) -> Result<Self, Error> {
+        if let Some(repo) = input.strip_prefix(github::PREFIX) {
+            online(config, &github::release_url(repo, config.tag.as_deref()))?;
+            let url = github::asset_url(repo, config.tag.as_deref(), config.download_timeout)?;
+            let temp_data = download(config, &url)?;
+            Self::guess_local(&temp_data)
+        } else if input.starts_with("http") {
+            let temp_data = download(config, input)?;
+            Self::guess_local(&temp_data)
+        } else {
+            Self::guess_local(input)
+        }
+    }
+
+    fn guess_local(input: &str) -> Result<Self, Error> {
+        let path = PathBuf::from(input);
+        let path = path.canonicalize().map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => Error::InputNotFound { path },
+            std::io::ErrorKind::PermissionDenied => Error::InputNotReadable { path },
+            _ => e.into(),
+        })?;
+
+        Ok(if path.is_ext("deb") {
+            PkgType::Deb(path)
+        } else if path.is_ext("yaml") || path.is_ext("yml") {
+            PkgType::Yaml(path)
+        } else if flatpak::is_export(&path) {
+            PkgType::Flatpak(path)
+        } else if path.is_file() && path.extension().is_none() {
+            // Downloads don't always come with an extension, look at the contents
+            Self::sniff(path)
+        } else {
+            PkgType::Other(path)
+        })
+    }
+
+    fn sniff(path: PathBuf) -> Self {
+        const DEB_MAGIC: &[u8] = b"!<arch>\ndebian-binary";
+
+        let Ok(contents) = fs::read(&path) else {
+            return PkgType::Other(path);
+        };
+
+        if contents.starts_with(DEB_MAGIC) {
+            PkgType::Deb(path)
+        } else if serde_yaml::from_slice::<serde_yaml::Value>(&contents)
+            .is_ok_and(|v| v.get("ingredients").is_some())
+        {
+            PkgType::Yaml(path)
+        } else {
+            PkgType::Other(path)
+        }
+    }
+}
+
+/// Describes a local deb for pkg2appimage, its dependencies are listed too so
+/// that they are bundled. The deb's own desktop file and icon are used when it
+/// has them
+fn deb_descriptor(input: &Path, desktop: Option<&deb::DesktopInfo>) -> Result<Pkg2AppimageDescriptor, Error> {
+    // The descriptor is YAML text, so the path has to be too
+    let deb = input.to_str().ok_or_else(|| Error::NonUtf8Path(input.to_path_buf()))?;
+    let file_name = input.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+    let name_reg = Regex::new("^[A-Za-z-0-9]*").unwrap();
+    let name = name_reg.find(file_name).map_or("", |m| m.as_str());
+
+    let depends = match deb::control(input) {
+        Ok(control) => deb::depends(&control),
+        Err(e) => {
+            eprintln!("Couldn't read the dependencies of {}, only the package itself will be bundled: {e}", input.display());
+            Vec::new()
+        }
+    };
+
+    Ok(Pkg2AppimageDescriptor {
+        app: name.to_string(),
+        ingredients: Pkg2AppimageDescriptorIngredients {
+            dist: Some("trusty".to_string()),
+            packages: std::iter::once(name.replace(' ', "-").to_lowercase()).chain(depends).unique().collect(),
+            sources: vec![
+                "deb http://archive.ubuntu.com/ubuntu/ trusty main universe".to_string()
+            ],
+            debs: vec![deb.to_string()],
+            ..Default::default()
+        },
+        // Runs inside the AppDir, which needs them at its root
+        script: match desktop {
+            Some(desktop) => std::iter::once(&desktop.desktop_file).chain(&desktop.icon).map(|file| format!("cp {file} .")).collect(),
+            None => vec!["ls".to_string()],
+        },
+    })
+}
+
+/// Writes a desktop file for a package that doesn't ship one, named after
+/// and launching its main binary, the files are left in the temporary folder
+fn binary_desktop(config: &Config, conf: &CliConf, binary: &deb::MainBinary) -> Result<deb::DesktopInfo, Error> {
+    let dir = temp::try_create("deb-desktop")?;
+    let name = binary.path.trim_start_matches("usr/bin/").to_string();
+
+    let icon = match &binary.icon {
+        Some(icon) => icon.clone(),
+        None => {
+            write_default_icon(&dir, config)?;
+            let png = dir.join("AppIcon.png");
+            let icon = if png.exists() { png } else { dir.join("AppIcon.svg") };
+            icon.to_string_lossy().into_owned()
+        }
+    };
+    let categories = conf.categories(config, &[])?;
+
+    let mut entry = DesktopFile::new(
+        name.clone(),
+        Path::new(&icon).file_stem().map(|s| s.to_string_lossy().into_owned()),
+        categories.clone(),
+        config.terminal,
+    );
+    // pkg2appimage's AppRun looks the command up in the AppDir's usr/bin
+    entry.file.exec = name.clone();
+    entry.file.try_exec = Some(name.clone());
+
+    let desktop_file = dir.join(format!("{name}.desktop"));
+    desktop_entry::to_writer(File::create(&desktop_file)?, &entry)?;
+
+    Ok(deb::DesktopInfo {
+        desktop_file: desktop_file.to_string_lossy().into_owned(),
+        name: Some(name),
+        categories,
+        icon: Some(icon),
+    })
+}
+
+/// Writes a generated descriptor to the temporary folder, so a hand-tuned
+/// `<name>.yaml` in the working directory is left alone
+fn write_descriptor(input: &Path, descriptor: &Pkg2AppimageDescriptor) -> Result<PathBuf, Error> {
+    let with_yaml_ext = input.with_extension("yaml");
+    let path = temp::try_create("descriptor")?.join(with_yaml_ext.file_name().unwrap_or_default());
+    to_writer(File::create(&path)?, descriptor)?;
+    Ok(path)
+}
+
+fn run_pkgtoappimage(config: &Config, conf: &CliConf, yml: &Path) -> Result<(), Error> {
+    let mut pkg2appimage = cmd::app_named(&conf.tool_names("pkg2appimage"), &conf.tool_dirs())
+        .ok_or_else(|| Error::ToolNotFound("pkg2appimage".to_string()))?;
+    if config.reproducible {
+        pkg2appimage.env("SOURCE_DATE_EPOCH", source_date_epoch().to_string());
+    }
+    pkg2appimage.arg(yml).run_outerr(config)
+}
+
+/// Downloads an icon with `download` and installs it as the AppDir's icon,
+/// raster images are converted to a 256x256 PNG
+fn install_icon_from_url<F>(url: &str, appdir: &Path, download: F) -> Result<(), Error>
+where
+    F: FnOnce(&Path) -> Result<(), Error>,
+{
+    let tmp_path = temp::try_create(&format!("icon-{}", cache::key(url)))?;
+    download(&tmp_path)?;
+    let icon = cache::first_file(&tmp_path).ok_or_else(|| Error::DownloadFailed(url.to_string()))?;
+
+    if icon.is_ext("svg") {
+        fs::copy(&icon, appdir.join("AppIcon.svg"))?;
+    } else {
+        // Downloads don't always have an extension
+        let img = image::ImageReader::open(&icon)?.with_guessed_format()?.decode()?;
+        resize(&img, 256, 256, image::imageops::FilterType::Lanczos3).save(appdir.join("AppIcon.png"))?;
+    }
+
+    fs::remove_dir_all(&tmp_path)?;
+    Ok(())
+}
+
+/// Prints a warning, strict builds fail with it instead
+fn warn(config: &Config, message: &str) -> Result<(), Error> {
+    if config.strict {
+        return Err(Error::Strict(message.to_string()));
+    }
+
+    eprintln!("Warning: {message}");
+    Ok(())
+}
+
+/// The license given in the config or found in `dir`, apps without one are
+/// marked as proprietary
+fn project_license(config: &Config, dir: &Path) -> Result<License, Error> {
+    if let Some(license) = &config.license {
+        return Ok(license.clone());
+    }
+
+    match License::locate(dir) {
+        Ok(license @ License::Custom(_)) => {
+            warn(config, &format!("the license wasn't recognized, it's written as {license}"))?;
+            Ok(license)
+        }
+        Ok(license) => Ok(license),
+        Err(licensing::Error::NoLicenseFile) => {
+            let license = License::custom("proprietary");
+            warn(config, &format!("no license file was found, it's written as {license}"))?;
+            Ok(license)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Warns when menus wouldn't know where to put the app
+fn check_categories(config: &Config, categories: &[String]) -> Result<(), Error> {
+    if categories.iter().any(|c| MAIN_CATEGORIES.contains(&c.as_str())) {
+        Ok(())
+    } else {
+        warn(config, &format!("none of the categories ({}) is a main one like {}", categories.join(", "), MAIN_CATEGORIES.join(", ")))
+    }
+}
+
+/// Makes sure a `--bundle-extra` glob parses and its destination stays
+/// inside the AppDir
+fn check_bundle_extra(pattern: &str, dest: &str) -> Result<(), Error> {
+    let invalid = |reason: &str| Error::InvalidBundleExtra { spec: format!("{pattern}:{dest}"), reason: reason.to_string() };
+    if let Err(e) = glob::Pattern::new(pattern) {
+        return Err(invalid(e.msg));
+    }
+    if !Path::new(dest).components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir)) {
+        return Err(invalid("the destination must be a relative path inside the AppDir"));
+    }
+    Ok(())
+}
+
+/// Copies what `pattern` matches to the `dest` directory inside the AppDir,
+/// keeping the layout below the pattern's first wildcard, relative patterns
+/// are looked up in the AppDir. Returns how many matches were copied
+fn bundle_extra(appdir: &Path, pattern: &str, dest: &str) -> Result<usize, Error> {
+    let pattern = appdir.join(pattern);
+    let mut base: PathBuf = pattern.components().take_while(|c| !c.as_os_str().to_string_lossy().contains(['*', '?', '['])).collect();
+    // Without wildcards the match itself goes inside `dest`, like with cp
+    if base == pattern {
+        base.pop();
+    }
+
+    let matches: Vec<PathBuf> = glob::glob(&pattern.to_string_lossy())
+        .map_err(|e| Error::InvalidBundleExtra { spec: format!("{}:{dest}", pattern.display()), reason: e.msg.to_string() })?
+        .collect::<Result<_, _>>()
+        .map_err(io::Error::from)?;
+    for path in &matches {
+        let target = appdir.join(dest).join(path.strip_prefix(&base).unwrap_or(path));
+        copy_all(path, &target)?;
+    }
+    Ok(matches.len())
+}
+
+/// Copies a file, or a directory with everything inside it
+fn copy_all(src: &Path, dst: &Path) -> io::Result<()> {
+    if !src.is_dir() {
+        fs::create_dir_all(dst.parent().unwrap_or(dst))?;
+        return fs::copy(src, dst).map(|_| ());
+    }
+
+    fs::create_dir_all(dst)?;
+    for entry in sorted_entries(src)? {
+        copy_all(&entry.path(), &dst.join(entry.file_name()))?;
+    }
+    Ok(())
+}
+
+/// `SOURCE_DATE_EPOCH` from the environment, 0 if it's not set
+fn source_date_epoch() -> u64 {
+    std::env::var("SOURCE_DATE_EPOCH").ok().and_then(|epoch| epoch.trim().parse().ok()).unwrap_or(0)
+}
+
+/// Sets the modification time of everything inside `dir` to `epoch`, symlinks
+/// are left alone since that would change their targets
+fn set_times(dir: &Path, epoch: u64) -> io::Result<()> {
+    let time = std::time::UNIX_EPOCH + Duration::from_secs(epoch);
+    for entry in sorted_entries(dir)? {
+        let file_type = entry.file_type()?;
+        if file_type.is_symlink() {
+            continue;
+        }
+        if file_type.is_dir() {
+            set_times(&entry.path(), epoch)?;
+        }
+        File::open(entry.path())?.set_modified(time)?;
+    }
+    File::open(dir)?.set_modified(time)
+}
+
+/// Writes the placeholder icon, strict builds only get it when it's explicitly
+/// allowed
+fn write_default_icon(appdir: &Path, config: &Config) -> Result<(), Error> {
+    use dialog::DialogBox;
+
+    if config.strict && !config.allow_default_icon {
+        return Err(Error::DefaultIcon);
+    }
+
+    // Strict mode has already refused it unless it's allowed
+    if !config.allow_default_icon {
+        warn(config, "no icon found, the AppImage will ship a generic placeholder icon")?;
+    }
+    if config.interactive {
+        dialog::Message::new("No icon found, writing one").show()?;
+    }
+
+    match &config.default_icon {
+        Some(path) => {
+            let invalid = |reason: String| Error::InvalidDefaultIcon { path: path.clone(), reason };
+            if path.is_ext("svg") {
+                let svg = fs::read(path).map_err(|e| invalid(e.to_string()))?;
+                fs::write(appdir.join("AppIcon.svg"), svg)?;
+            } else {
+                let img = image::open(path).map_err(|e| invalid(e.to_string()))?;
+                img.save(appdir.join("AppIcon.png"))?;
+            }
+        }
+        None => File::create(appdir.join("AppIcon.svg"))?.write_all(DEFAULT_ICON)?,
+    }
+    Ok(())
+}
+
+/// The configured rating profile, the user picks one in interactive mode and
+/// otherwise no objectionable content is assumed
+fn rating_profile(config: &Config) -> Result<RatingProfile, Error> {
+    use dialog::DialogBox;
+
+    if let Some(profile) = config.rating_profile {
+        return Ok(profile);
+    }
+    if !config.interactive {
+        return Ok(RatingProfile::default());
+    }
+
+    let question = format!(
+        "Which content rating fits the app? ({})",
+        RatingProfile::NAMES.join(", ")
+    );
+    loop {
+        let answer = dialog::Input::new(&question)
+            .title("Content rating")
+            .default("none")
+            .show()?
+            .ok_or(Error::Cancelled)?;
+
+        match answer.trim().parse() {
+            Ok(profile) => return Ok(profile),
+            Err(e) => dialog::Message::new(e).show()?,
+        }
+    }
+}
+
+/// Writes the desktop file and the AppStream metadata under the component's
+/// id, in the layout appimagetool and AppStream expect, returns the paths of
+/// both
+fn install_metadata(appdir: &Path, entry: &DesktopFile, appstream: &AppStream) -> Result<(PathBuf, PathBuf), Error> {
+    let desktop_file = entry.write(appdir, &appstream.component.id)?;
+    let metainfo = appstream.write(appdir)?;
+    Ok((desktop_file, metainfo))
+}
+
+/// Copies `files` and the AppDir's icon to `dir`
+fn export_metadata(appdir: &Path, dir: &Path, files: &[&Path]) -> Result<(), Error> {
+    fs::create_dir_all(dir)?;
+    let icons = ["AppIcon.png", "AppIcon.svg"].map(|i| appdir.join(i));
+    for file in files.iter().copied().chain(icons.iter().map(PathBuf::as_path)).filter(|f| f.exists()) {
+        fs::copy(file, dir.join(file.file_name().unwrap_or_default()))?;
+    }
+    Ok(())
+}
+
+/// Copies the folder `src` as `dst`, symlinks are kept as they are
+fn copy_tree(src: &Path, dst: &Path) -> io::Result<()> {
+    fs::create_dir_all(dst)?;
+    for entry in sorted_entries(src)? {
+        let target = dst.join(entry.file_name());
+        if entry.file_type()?.is_symlink() {
+            std::os::unix::fs::symlink(fs::read_link(entry.path())?, target)?;
+        } else if entry.file_type()?.is_dir() {
+            copy_tree(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Copies the whole AppDir to `dir`
+fn write_appdir(appdir: &Path, dir: &Path, config: &Config) -> Result<(), Error> {
+    check_output(dir, config.force, config.interactive)?;
+    if dir.exists() {
+        fs::remove_dir_all(dir)?;
+    }
+    Ok(copy_tree(appdir, dir)?)
+}
+
+/// The folder to build in: a copy in the temporary folder when the build
+/// changes the app's own files or adds some of its own and `appdir` is the
+/// user's, otherwise `appdir`
+fn staged_appdir(config: &Config, appdir: PathBuf) -> Result<PathBuf, Error> {
+    if !config.patch_interpreter && !config.bundle_libs && config.bundle_extra.is_empty() {
+        return Ok(appdir);
+    }
+    stage_copy(&appdir)
+}
+
+/// Copies a folder of the user's to the temporary folder, one that's already
+/// there is used as it is
+fn stage_copy(appdir: &Path) -> Result<PathBuf, Error> {
+    if temp::contains(appdir) {
+        return Ok(appdir.to_path_buf());
+    }
+
+    let copy = temp::get("staged").join(appdir.file_name().unwrap_or_default());
+    if copy.exists() {
+        fs::remove_dir_all(&copy)?;
+    }
+    copy_tree(appdir, &copy)?;
+    eprintln!("Building in a copy of '{}' at '{}', so its files are left untouched", appdir.display(), copy.display());
+    Ok(copy)
+}
+
+/// The AppDir with every time set to `epoch`, a folder of the user's is
+/// copied first so its times are left alone
+fn normalized_appdir(appdir: &Path, epoch: u64) -> Result<PathBuf, Error> {
+    let staged = stage_copy(appdir)?;
+    set_times(&staged, epoch)?;
+    Ok(staged)
+}
+
+/// The long description, read from `description_file` if there's one
+fn description(config: &Config) -> Result<String, Error> {
+    match &config.description_file {
+        Some(path) => fs::read_to_string(path).map_err(|source| Error::DescriptionFile { path: path.clone(), source }),
+        None => Ok(config.description.clone().unwrap_or_else(|| "TODO!TODO!".to_string())),
+    }
+}
+
+/// Names the shell can export: letters, digits and '_', not starting with a
+/// digit
+fn is_env_name(name: &str) -> bool {
+    name.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_') && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Quotes `value` for sh, single quotes keep everything literal
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// An AppRun script for `executable`, which is relative to the AppDir, it
+/// exports `env` and with `bundled_libs` makes `usr/lib` visible
+fn apprun(executable: &Path, bundled_libs: bool, env: &[(String, String)]) -> String {
+    let mut script = "#!/bin/sh\nHERE=\"$(dirname \"$(readlink -f \"$0\")\")\"\n".to_string();
+    if bundled_libs {
+        script += "export LD_LIBRARY_PATH=\"$HERE/usr/lib${LD_LIBRARY_PATH:+:$LD_LIBRARY_PATH}\"\n";
+    }
+    for (name, value) in env {
+        script += &format!("export {name}={}\n", shell_quote(value));
+    }
+    script + &format!("exec \"$HERE/{}\" \"$@\"\n", executable.display())
+}
+
+/// The metainfo the app already ships, one that can't be read is replaced
+fn shipped_metainfo(config: &Config, appdir: &Path) -> Result<Option<appstream::Shipped>, Error> {
+    let Some(file) = metainfo_files(appdir).into_iter().next() else {
+        return Ok(None);
+    };
+    match appstream::Shipped::read(&file) {
+        Ok(shipped) => Ok(Some(shipped)),
+        Err(e) => {
+            warn(config, &format!("{e}, '{}' is replaced", file.display()))?;
+            Ok(None)
+        }
+    }
+}
+
+/// A launcher shipped with the app, it's only taken if it can be run
+fn existing_apprun(appdir: &Path) -> Option<PathBuf> {
+    let apprun = appdir.join("AppRun");
+    let metadata = fs::metadata(&apprun).ok()?;
+    (metadata.is_file() && metadata.permissions().mode() & 0o111 != 0).then_some(apprun)
+}
+
+/// The explicit translations of a text, plus the untagged one under
+/// `default_lang` unless that language is already translated
+fn translations(default: &str, explicit: &[(String, String)], default_lang: Option<&str>) -> BTreeMap<String, String> {
+    let mut translations: BTreeMap<String, String> = explicit.iter().cloned().collect();
+    if let Some(lang) = default_lang {
+        translations.entry(lang.to_string()).or_insert_with(|| default.to_string());
+    }
+    translations
+}
+
+/// Size and hash of a produced AppImage
+#[derive(Debug, PartialEq)]
+struct Summary {
+    size: u64,
+    sha256: String,
+}
+
+/// Measures the AppImage and optionally writes its hash in `sha256sum`'s
+/// format
+fn summarize(output: &Path, write_checksum: bool) -> Result<Summary, Error> {
+    let summary = Summary {
+        size: fs::metadata(output)?.len(),
+        sha256: cache::sha256(output)?,
+    };
+
+    if write_checksum {
+        let mut checksum_file = output.as_os_str().to_owned();
+        checksum_file.push(".sha256");
+        let file_name = output.file_name().unwrap_or_default().to_string_lossy();
+        fs::write(checksum_file, format!("{}  {file_name}\n", summary.sha256))?;
+    }
+
+    Ok(summary)
+}
+
+/// Extracts archives and, both for archives and directories, goes into the
+/// top-level directory when it's the only thing inside. An archive that
+/// only contains another archive is extracted too, up to a few levels
+fn prepare_input(config: &Config, input: PathBuf) -> Result<PathBuf, Error> {
+    let limits = &archive::Limits { max_size: config.max_extract_size, timeout: config.timeout, ..Default::default() };
+    let mut input = input;
+    let mut depth = 0;
+    loop {
+        let dir = if archive::is_archive(&input) {
+            depth += 1;
+            extract_to_temp(&input, depth, limits)?
+        } else {
+            input
+        };
+
+        match top_level(&dir)? {
+            TopLevel::Many | TopLevel::File(_) => return Ok(dir),
+            TopLevel::Dir(inner) => return Ok(inner),
+            TopLevel::Archive(nested) if depth >= MAX_ARCHIVE_DEPTH => {
+                warn(config, &format!("'{}' is not extracted, archives are only extracted {MAX_ARCHIVE_DEPTH} levels deep", nested.display()))?;
+                return Ok(dir);
+            }
+            TopLevel::Archive(nested) => input = nested,
+        }
+    }
+}
+
+/// What's inside a folder or an extracted archive, as far as collapsing it goes
+#[derive(Debug, PartialEq)]
+enum TopLevel {
+    /// Nothing or several entries, the folder is used as is
+    Many,
+    /// A lone folder, which is used instead
+    Dir(PathBuf),
+    /// A lone file, like a single binary, the folder is kept around it
+    File(PathBuf),
+    /// A lone archive that has to be extracted too
+    Archive(PathBuf),
+}
+
+fn top_level(dir: &Path) -> Result<TopLevel, Error> {
+    let Some(entry) = lone_entry(dir)? else {
+        return Ok(TopLevel::Many);
+    };
+
+    // Symlinks are classified by what they point to
+    let path = entry.path();
+    Ok(if path.is_dir() {
+        TopLevel::Dir(path)
+    } else if archive::is_archive(&path) {
+        TopLevel::Archive(path)
+    } else {
+        TopLevel::File(path)
+    })
+}
+
+const MAX_ARCHIVE_DEPTH: usize = 3;
+
+fn extract_to_temp(input: &Path, depth: usize, limits: &archive::Limits) -> Result<PathBuf, Error> {
+    let stem = input
+        .file_stem()
+        .map(|s| s.to_str().unwrap_or(""))
+        .unwrap_or("archive_out");
+
+    // Inner archives usually share the outer one's name
+    let tmp_path = if depth > 1 {
+        temp::try_create(&format!("{stem}-{depth}"))?
+    } else {
+        temp::try_create(stem)?
+    };
+
+    // Clean any leftover temporary files, this makes using unarchiver
+    // way easier
+    if tmp_path.exists() {
+        std::fs::remove_dir_all(&tmp_path)?;
+    }
+    fs::create_dir_all(&tmp_path)?;
+
+    archive::unarchive(input, &tmp_path, limits)?;
+    Ok(tmp_path)
+}
+
+/// Where an AppDir's icon comes from when none is given
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(tag = "source", content = "path", rename_all = "kebab-case")]
+pub enum IconSource {
+    /// An `AppIcon.png` or `AppIcon.svg` that's already there
+    AppIcon(PathBuf),
+
+    /// The icon named by a bundled desktop file
+    Desktop(PathBuf),
+
+    /// Extracted from a Windows executable
+    Exe(PathBuf),
+
+    /// Given with `--icon`
+    File(PathBuf),
+
+    /// Downloaded from `--icon-url`, the URL goes in `path`
+    Url(String),
+
+    /// Some other image that looks like an icon, like a `logo.png`
+    Image(PathBuf),
+
+    /// The placeholder icon
+    Default,
+}
+
+impl IconSource {
+    /// Images named after the app are preferred to other loose ones
+    fn detect(appdir: &Path, name: &str) -> Self {
+        if let Some(icon) = ["AppIcon.png", "AppIcon.svg"].iter().map(|i| appdir.join(i)).find(|i| i.exists()) {
+            IconSource::AppIcon(icon)
+        } else if let Some(bundled) = bundled_desktop_icon(appdir) {
+            IconSource::Desktop(bundled)
+        } else if let Some(exe) = look_for_ext(appdir, "exe") {
+            IconSource::Exe(exe)
+        } else if let Some(image) = loose_icon(appdir, name) {
+            IconSource::Image(image)
+        } else {
+            IconSource::Default
+        }
+    }
+}
+
+/// Square PNGs smaller than this are too blurry for an icon
+const MIN_ICON_SIZE: u32 = 32;
+
+/// The best image in the AppDir's root or hicolor theme that could be an icon:
+/// one named like the app, then scalable ones and then the biggest
+fn loose_icon(appdir: &Path, name: &str) -> Option<PathBuf> {
+    let hicolor = appdir.join("usr").join("share").join("icons").join("hicolor");
+    let themed = fs::read_dir(hicolor).into_iter().flatten().flatten().map(|size_dir| size_dir.path().join("apps"));
+
+    std::iter::once(appdir.to_path_buf())
+        .chain(themed)
+        .flat_map(|dir| visible_files(&dir))
+        .filter_map(|p| icon_size(&p).map(|size| (p, size)))
+        .max_by_key(|(p, size)| {
+            let named = p.file_stem().is_some_and(|stem| stem.to_string_lossy().eq_ignore_ascii_case(name));
+            (named, *size, std::cmp::Reverse(p.clone()))
+        })
+        .map(|(p, _)| p)
+}
+
+// Scalable icons count as the biggest, non-square images can't be icons
+fn icon_size(path: &Path) -> Option<u32> {
+    if path.is_ext("svg") {
+        Some(u32::MAX)
+    } else if path.is_ext("png") {
+        let (width, height) = image::image_dimensions(path).ok()?;
+        (width == height && width >= MIN_ICON_SIZE).then_some(width)
+    } else {
+        None
+    }
+}
+
+/// The executable of an AppDir, when there are several candidates the user
+/// has to pick one
+enum ExecutableGuess {
+    Found(PathBuf),
+
+    /// Sorted by name length, usually the one we want has the shortest
+    Ambiguous(Vec<PathBuf>),
+}
+
+/// `path` relative to `base`, for showing it to the user
+fn display_relative(base: &Path, path: &Path) -> String {
+    path.strip_prefix(base).unwrap_or(path).to_string_lossy().into_owned()
+}
+
+/// The candidate the user chose, either by its number (starting at 1) or by its
+/// path relative to `base`
+fn pick_candidate<'a>(choice: &str, candidates: &'a [PathBuf], base: &Path) -> Option<&'a PathBuf> {
+    let choice = choice.trim();
+    match choice.parse::<usize>() {
+        Ok(n) => n.checked_sub(1).and_then(|i| candidates.get(i)),
+        Err(_) => candidates.iter().find(|p| display_relative(base, p) == choice),
+    }
+}
+
+fn guess_executable(dir: &Path, conf: &CliConf) -> Result<ExecutableGuess, Error> {
+    let dir = dir.to_path_buf();
+    if let Some(file) = lone_file(&dir) {
+        return Ok(ExecutableGuess::Found(file));
+    } else if let Some(shell_file) = look_for_ext(&dir, "sh") {
+        return Ok(ExecutableGuess::Found(shell_file));
+    } else if let Some(linux_exe) = look_for_ext(&dir, "x86_64") {
+        return Ok(ExecutableGuess::Found(linux_exe));
+    }
+
+    let mut exes = look_for_no_exts(&dir)?;
+    if exes.is_empty() {
+        Err(Error::NoExecutable)
+    } else if exes.len() == 1 {
+        Ok(ExecutableGuess::Found(exes.remove(0)))
+    } else if let Some(preferred) = preferred_executable(&exes, &conf.prefer_executables) {
+        Ok(ExecutableGuess::Found(preferred))
+    } else {
+        // look_for_no_exts already refused paths without a file name
+        exes.sort_by_key(|e| e.file_name().map(|name| name.to_string_lossy().len()));
+        Ok(ExecutableGuess::Ambiguous(exes))
+    }
+}
+
+/// What a build produced, see `Config::json_report`
+#[derive(Debug, Default, Serialize)]
+pub struct Report {
+    /// The AppImage, or the directory with `export_metadata` or `appdir_out`
+    pub output: PathBuf,
+
+    // Packages built by pkg2appimage only have some of these
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub license: Option<License>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub executable: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon: Option<IconSource>,
+
+    /// Only when the AppImage was actually produced
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+}
+
+/// Writes the outcome of a build as JSON to `path`, `-` is stdout
+fn write_report(path: &Path, report: &Result<Report, Error>) -> Result<(), Error> {
+    let json = match report {
+        Ok(report) => serde_json::to_string_pretty(report),
+        Err(e) => serde_json::to_string_pretty(&serde_json::json!({
+            "error": { "message": e.to_string(), "exit_code": e.exit_code() }
+        })),
+    }
+    .map_err(std::io::Error::from)?;
+
+    if path == Path::new("-") {
+        println!("{json}");
+    } else {
+        fs::write(path, json + "\n")?;
+    }
+    Ok(())
+}
+
+/// What would be used to build an AppDir, found without changing anything
+#[derive(Debug, Serialize)]
+pub struct Detection {
+    /// With several candidates, the one offered by default
+    pub executable: PathBuf,
+
+    /// Every candidate when the executable is ambiguous
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub candidates: Vec<PathBuf>,
+    pub icon: IconSource,
+    pub license: Option<License>,
+    pub categories: Vec<String>,
+}
+
+/// Runs the detection a build would over a directory, archives aren't
+/// extracted and no file is written
+pub fn inspect_dir(dir: &Path) -> Result<Detection, Error> {
+    let conf = CliConf::default();
+    let (executable, candidates) = match guess_executable(dir, &conf)? {
+        ExecutableGuess::Found(executable) => (executable, Vec::new()),
+        ExecutableGuess::Ambiguous(candidates) => (candidates[0].clone(), candidates),
+    };
+
+    Ok(Detection {
+        executable,
+        candidates,
+        icon: IconSource::detect(dir, &dir.file_name().unwrap_or_default().to_string_lossy()),
+        license: License::locate(dir).ok(),
+        categories: conf.categories(&Config::new(dir.to_string_lossy()), &[])?,
+    })
+}
+
+fn lone_entry(dir: &Path) -> std::io::Result<Option<fs::DirEntry>> {
+    let mut entries = fs::read_dir(dir)?;
+    let first = entries.next().transpose()?;
+    Ok(first.filter(|_| entries.next().is_none()))
+}
+
+/// The only thing inside `dir` when it's a regular file, archives with a
+/// single binary end up like this
+fn lone_file(dir: &Path) -> Option<PathBuf> {
+    let entry = lone_entry(dir).ok()??;
+    entry.file_type().ok()?.is_file().then(|| entry.path())
+}
+
+/// Shows the desktop entry and lets the user change its name and whether it
+/// runs in a terminal, only in interactive mode
+fn review_entry(config: &Config, entry: &mut DesktopEntry) -> Result<(), Error> {
+    use dialog::DialogBox;
+
+    if !config.interactive {
+        return Ok(());
+    }
+
+    let summary = format!("Name: {}\nCategories: {}\nTerminal: {}", entry.name, entry.categories.join(", "), entry.terminal);
+    let name = dialog::Input::new(format!("{summary}\n\nName of the app:"))
+        .title("Desktop entry")
+        .default(&entry.name)
+        .show()?
+        .ok_or(Error::Cancelled)?;
+    if !name.trim().is_empty() {
+        entry.name = name.trim().to_string();
+    }
+
+    let terminal = dialog::Question::new("Does the app run inside a terminal?").title("Desktop entry").show()?;
+    entry.terminal = matches!(terminal, dialog::Choice::Yes);
+    Ok(())
+}
+
+/// Makes sure an existing AppImage is only overwritten when the user wants to
+fn check_output(output: &Path, force: bool, interactive: bool) -> Result<(), Error> {
+    use dialog::DialogBox;
+
+    if force || !output.exists() {
+        return Ok(());
+    }
+
+    if interactive {
+        let choice = dialog::Question::new(format!("'{}' already exists, overwrite it?", output.display()))
+            .title("Overwrite AppImage?")
+            .show()?;
+        if matches!(choice, dialog::Choice::Yes) {
+            return Ok(());
+        }
+    }
+
+    Err(Error::OutputExists(output.to_path_buf()))
+}
+
+fn check_gpg<F: Fn(&str) -> bool>(installed: F) -> Result<(), Error> {
+    if ["gpg", "gpg2"].into_iter().any(installed) {
+        Ok(())
+    } else {
+        Err(Error::GpgNotFound)
+    }
+}
+
+/// At least two segments that don't start with a digit, as AppStream wants
+fn is_app_id(id: &str) -> bool {
+    let segments = id.split('.').collect::<Vec<_>>();
+    segments.len() >= 2
+        && segments.iter().all(|s| {
+            s.chars().next().is_some_and(|c| !c.is_ascii_digit()) && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        })
+}
+
+fn is_extension_key(key: &str) -> bool {
+    key.strip_prefix("X-").is_some_and(|rest| !rest.is_empty() && rest.chars().all(|c| c.is_ascii_alphanumeric() || c == '-'))
+}
+
+/// The `X-` keys of the desktop file, the AppImage ones are filled in unless
+/// they were given explicitly
+fn extension_keys(config: &Config, name: &str, version: Option<&str>) -> BTreeMap<String, String> {
+    let mut keys: BTreeMap<String, String> = config.x_keys.iter().cloned().collect();
+    keys.entry("X-AppImage-Name".to_string()).or_insert_with(|| name.to_string());
+    if let Some(version) = version {
+        keys.entry("X-AppImage-Version".to_string()).or_insert_with(|| version.to_string());
+    }
+    keys
+}
+
+/// `<name>-<version>-<arch>.AppImage`, without the version if it's unknown
+fn output_name(name: &str, version: Option<&str>) -> PathBuf {
+    let name = name.replace(' ', "_");
+    let arch = std::env::consts::ARCH;
+    match version {
+        Some(version) => PathBuf::from(format!("{name}-{version}-{arch}.AppImage")),
+        None => PathBuf::from(format!("{name}-{arch}.AppImage")),
+    }
+}
+
+/// Update transports AppImageUpdate knows, with how many fields they take
+const UPDATE_TRANSPORTS: [(&str, usize); 3] = [("zsync", 1), ("gh-releases-zsync", 4), ("pling-v1-zsync", 2)];
+
+/// Only the transport and the number of fields are checked
+fn is_update_information(info: &str) -> bool {
+    let mut fields = info.split('|');
+    let transport = fields.next().unwrap_or_default();
+    let fields = fields.collect::<Vec<_>>();
+
+    UPDATE_TRANSPORTS.iter().any(|(name, len)| *name == transport && fields.len() == *len)
+        && fields.iter().all(|f| !f.trim().is_empty())
+}
+
+/// The sizes mksquashfs takes
+fn is_block_size(kib: u32) -> bool {
+    (4..=1024).contains(&kib) && kib.is_power_of_two()
+}
+
+/// What appimagetool passes on to mksquashfs, one argument each
+fn mksquashfs_opts(config: &Config) -> Vec<String> {
+    let block_size = config.block_size.into_iter().flat_map(|kib| ["-b".to_string(), format!("{kib}K")]);
+    // mksquashfs takes the times from SOURCE_DATE_EPOCH, the owners and
+    // extended attributes of the build host would still leak in
+    let reproducible = config.reproducible.then_some(["-all-root", "-no-xattrs"]).into_iter().flatten().map(str::to_string);
+    let extra = config.mksquashfs_extra.iter().flat_map(|args| args.split_whitespace().map(str::to_string));
+    block_size.chain(reproducible).chain(extra).collect()
+}
+
+fn appimagetool_args(config: &Config, appdir: &Path, output: &Path) -> Vec<OsString> {
+    let mut args: Vec<OsString> = Vec::new();
+    if config.no_appstream_validate {
+        args.push("-n".into());
+    }
+    if let Some(compression) = config.compression {
+        args.extend(["--comp".into(), compression.name().into()]);
+    }
+    for opt in mksquashfs_opts(config) {
+        args.extend(["--mksquashfs-opt".into(), opt.into()]);
+    }
+    if let Some(info) = &config.update_information {
+        args.extend(["-u".into(), info.into()]);
+    }
+    if config.signs() {
+        args.push("--sign".into());
+    }
+    if let Some(key) = &config.sign_key {
+        args.extend(["--sign-key".into(), key.into()]);
+    }
+
+    args.extend([appdir.into(), output.into()]);
+    args
+}
+
+/// pkg2appimage leaves its results inside `out`
+fn pkg2appimage_output(dry_run: bool) -> Result<PathBuf, Error> {
+    let out = PathBuf::from("out");
+    if dry_run {
+        Ok(out)
+    } else {
+        look_for_ext(&out, "AppImage").ok_or(Error::NoAppImageProduced)
+    }
+}
+
+/// The first bytes of `path`, enough to tell ELF binaries and scripts apart
+fn file_start(path: &Path) -> std::io::Result<Vec<u8>> {
+    use std::io::Read;
+
+    let mut start = Vec::with_capacity(4);
+    File::open(path)?.take(4).read_to_end(&mut start)?;
+    Ok(start)
+}
+
+/// Whether `path` starts like something Linux can run: an ELF binary or a
+/// script with a shebang
+fn is_runnable(path: &Path) -> std::io::Result<bool> {
+    let start = file_start(path)?;
+    Ok(start == b"\x7fELF" || start.starts_with(b"#!"))
+}
+
+fn is_elf(path: &Path) -> std::io::Result<bool> {
+    Ok(file_start(path)? == b"\x7fELF")
+}
+
+/// Reports where the executable gets its loader and libraries from, warning
+/// about what only exists on the build host unless it's patched
+fn check_elf_paths(config: &Config, appdir: &Path, executable: &Path, paths: &libs::Paths) -> Result<(), Error> {
+    eprintln!(
+        "'{}' uses the interpreter {} and the RPATH '{}'",
+        executable.display(),
+        paths.interpreter.as_deref().unwrap_or("(none)"),
+        paths.rpath.join(":")
+    );
+
+    let host_interpreter = paths.host_interpreter();
+    let host_rpath = paths.host_rpath();
+    if host_interpreter.is_none() && host_rpath.is_empty() {
+        return Ok(());
+    }
+    if config.patch_interpreter {
+        return libs::patch(config, appdir, executable, paths);
+    }
+
+    if let Some(interpreter) = host_interpreter {
+        warn(config, &format!("the interpreter '{interpreter}' only exists on this system, fix it with --patch-interpreter"))?;
+    }
+    if !host_rpath.is_empty() {
+        warn(config, &format!("the RPATH '{}' only exists on this system, fix it with --patch-interpreter", host_rpath.join(":")))?;
+    }
+    Ok(())
+}
+
+/// Catches a data file picked as the executable before it becomes the AppRun
+fn check_executable(config: &Config, path: &Path) -> Result<(), Error> {
+    if is_runnable(path)? {
+        Ok(())
+    } else {
+        warn(config, &format!("'{}' is neither an ELF binary nor a script with a shebang, the AppImage won't start, pick another one with --exec", path.display()))
+    }
+}
+
+fn mark_executable(path: &Path) -> std::io::Result<()> {
+    let mut perms = std::fs::metadata(path)?.permissions();
+    let mode = perms.mode();
+    let user_perms = ((mode/64) | 1) * 64;
+    let group_perms = (((mode % 64)/8) | 1) * 8;
+    let others_perms = (mode % 8) | 1 ;
+    perms.set_mode(user_perms + group_perms + others_perms);
+    fs::set_permissions(path, perms)
+}
+
+mod cmd {
+    #[cfg(not(feature = "net"))]
+    use std::{path::Path, time::Duration};
+    use std::{
+        io::Read,
+        os::unix::fs::PermissionsExt,
+        path::PathBuf,
+        process::{Child, Command, ExitStatus, Stdio},
+        thread::JoinHandle,
+        time::Instant,
+    };
+
+    use crate::{mark_executable, CliKind, Config, Error};
+
+    pub fn app(name: &str) -> Option<Command> {
+        which::which(name).ok().map(Command::new)
+    }
+
+    /// The first of `candidates` that `exists` says is installed
+    pub fn resolve<F: Fn(&str) -> bool>(candidates: &[String], exists: F) -> Option<&str> {
+        candidates.iter().map(String::as_str).find(|name| exists(name))
+    }
+
+    /// Finds `name` in PATH or else in one of `dirs`
+    pub fn locate(name: &str, dirs: &[PathBuf]) -> Option<PathBuf> {
+        which::which(name).ok().or_else(|| {
+            dirs.iter()
+                .map(|dir| dir.join(name))
+                .find(|p| p.metadata().is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0))
+        })
+    }
+
+    /// Finds a tool that may be installed under any of `names`, in PATH or
+    /// in `dirs`
+    pub fn app_named(names: &[String], dirs: &[PathBuf]) -> Option<Command> {
+        names.iter().find_map(|name| locate(name, dirs)).map(Command::new)
+    }
+
+    /// Inside a container the tool can't be looked for, so the first name is
+    /// used
+    pub fn app_from(names: &[String], kind: CliKind, container: Option<&str>, dirs: &[PathBuf]) -> Result<Command, Error> {
+        let missing = || Error::ToolNotFound(names.first().cloned().unwrap_or_default());
+        match (kind, container) {
+            (CliKind::Native, _) => app_named(names, dirs).ok_or_else(missing),
+            (_, Some(container)) => names.first().map(|name| app_from_toolbox(container, name)).ok_or_else(missing),
+            (_, None) => Err(Error::NoContainer),
+        }
+    }
+
+    /// Toolbox containers are podman ones, so podman knows about them
+    pub fn container_exists(name: &str) -> Result<bool, Error> {
+        let status = app("podman")
+            .ok_or_else(|| Error::ToolNotFound("podman".to_string()))?
+            .args(["container", "exists", name])
+            .status()?;
+        Ok(status.success())
+    }
+
+    fn app_from_toolbox(container: &str, command: &str) -> Command {
+        let mut c = Command::new("/usr/bin/toolbox");
+        c.arg("run").arg("-c").arg(container).arg(command);
+        c
+    }
+    
+    #[cfg(not(feature = "net"))]
+    pub fn download_as(url: &str, file_name: &Path, timeout: Option<Duration>) -> Result<(), Error> {
+        let secs = timeout.map(|t| t.as_secs().max(1).to_string());
+        if let Some(mut curl) = app("curl") {
+            curl.args(["-L", url, "-o"]).arg(file_name);
+            if let Some(secs) = &secs {
+                curl.args(["--max-time", secs]);
+            }
+            run_with(&mut curl, Duration::MAX)
+        } else if let Some(mut wget) = app("wget") {
+            wget.args([url, "-O"]).arg(file_name);
+            if let Some(secs) = &secs {
+                wget.args(["--timeout", secs]);
+            }
+            run_with(&mut wget, Duration::MAX)
+        } else {
+            Err(Error::NoDownloader)
+        }
+    }
+
+    #[cfg(not(feature = "net"))]
+    pub fn download_to(url: &str, dir_path: &Path, timeout: Option<Duration>) -> Result<Command, Error> {
+        let secs = timeout.map(|t| t.as_secs().max(1).to_string());
+        if let Some(mut curl) = app("curl") {
+            curl.args(["-O", "-L", "--output-dir"]).arg(dir_path).arg(url);
+            if let Some(secs) = &secs {
+                curl.args(["--max-time", secs]);
+            }
+            Ok(curl)
+        } else if let Some(mut wget) = app("wget") {
+            wget.arg(url).arg("-P").arg(dir_path);
+            if let Some(secs) = &secs {
+                wget.args(["--timeout", secs]);
+            }
+            Ok(wget)
+        } else {
+            Err(Error::NoDownloader)
+        }
+    }
+
+ 
+    
+    const CACHE_DIR: &str = ".local/Apps";
+
+    pub struct  LinkSet {
+        pub x86_64: &'static str,
+        pub arch64: &'static str
+    }
+
+    impl LinkSet {
+        pub fn get_current(&self) -> Result<&str, Error> {
+            match std::env::consts::ARCH {
+                "aarch64"=> Ok(self.arch64),
+                "x86_64" => Ok(self.x86_64),
+                arch => Err(Error::ArchitectureNotSupported(arch.to_string()))
+            }
+        }
+    }
+
+    /// The installed or cached app, downloaded unless `offline`
+    pub fn cached_app(name: &str, link_set: &LinkSet, timeout: Option<std::time::Duration>, offline: bool) -> Result<Command, Error> {
+        if let Some(app) = app(name) {
+            return Ok(app);
+        }
+
+        let cache_dir = directories::UserDirs::new().ok_or_else(|| Error::NoHomeDir(name.to_string()))?.home_dir().join(CACHE_DIR);
+        if !cache_dir.exists() {
+            std::fs::create_dir_all(&cache_dir)?;
+        }
+
+        let cached_app = cache_dir.join(name);
+        if let Ok(app) = which::which(&cached_app) {
+            return Ok(Command::new(app));
+        }
+
+        let url = link_set.get_current()?;
+        if offline {
+            return Err(Error::Offline(url.to_string()));
+        }
+        crate::download_file(url, &cached_app, timeout)?;
+        mark_executable(&cached_app)?;
+        Ok(Command::new(cached_app))
+    }
+
+    pub const DEFAULT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(300);
+    const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(50);
+
+    fn program(cmd: &Command) -> String {
+        cmd.get_program().to_string_lossy().into_owned()
+    }
+
+    /// Waits for `child` up to `timeout`, killing it once it's exceeded
+    fn wait_for(child: &mut Child, cmd: &Command, timeout: std::time::Duration) -> Result<ExitStatus, Error> {
+        let start = Instant::now();
+        loop {
+            if let Some(status) = child.try_wait()? {
+                return Ok(status);
+            }
+
+            if start.elapsed() >= timeout {
+                // It might have just finished, in which case there's nothing
+                // to kill
+                let _ = child.kill();
+                child.wait()?;
+                return Err(Error::CommandTimedOut { program: program(cmd) });
+            }
+
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    // Pipes have to be drained while waiting, otherwise a chatty program
+    // blocks once they are full
+    fn read_in_background<R: Read + Send + 'static>(mut reader: R) -> JoinHandle<Vec<u8>> {
+        std::thread::spawn(move || {
+            let mut buf = Vec::new();
+            let _ = reader.read_to_end(&mut buf);
+            buf
+        })
+    }
+
+    pub fn run_with(cmd: &mut Command, timeout: std::time::Duration) -> Result<(), Error> {
+        let mut child = cmd.spawn()?;
+        if wait_for(&mut child, cmd, timeout)?.success() {
+            Ok(())
+        } else {
+            Err(Error::CommandFailed {
+                program: program(cmd),
+                output: String::new(),
+            })
+        }
+    }
+
+    /// Both streams of a command, each under its name and left out if empty
+    fn captured(stdout: &[u8], stderr: &[u8]) -> String {
+        [("stdout", stdout), ("stderr", stderr)]
+            .into_iter()
+            .map(|(name, out)| (name, String::from_utf8_lossy(out)))
+            .filter(|(_, out)| !out.trim().is_empty())
+            .map(|(name, out)| format!("{name}:\n{}", out.trim_end()))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Runs `cmd` with its output captured, which is part of the error if it
+    /// fails and is printed on success in verbose mode
+    pub fn run_outerr_with(cmd: &mut Command, timeout: std::time::Duration, verbose: bool) -> Result<(), Error> {
+        let mut child = cmd.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+        let stdout = child.stdout.take().map(read_in_background);
+        let stderr = child.stderr.take().map(read_in_background);
+
+        let status = wait_for(&mut child, cmd, timeout)?;
+        let stdout = stdout.and_then(|h| h.join().ok()).unwrap_or_default();
+        let stderr = stderr.and_then(|h| h.join().ok()).unwrap_or_default();
+        let output = captured(&stdout, &stderr);
+        if status.success() {
+            if verbose && !output.is_empty() {
+                eprintln!("'{}' output\n{output}", program(cmd));
+            }
+            Ok(())
+        } else {
+            Err(Error::CommandFailed { program: program(cmd), output })
+        }
+    }
+
+    /// Runs commands within `config`'s timeout, their output is printed in
+    /// verbose mode
+    pub trait RunExt {
+        fn run(&mut self, config: &Config) -> Result<(), Error>;
+        fn run_outerr(&mut self, config: &Config) -> Result<(), Error>;
+    }
+
+    impl RunExt for &mut Command {
+        fn run(&mut self, config: &Config) -> Result<(), Error> {
+            run_with(self, config.timeout)
+        }
+
+        fn run_outerr(&mut self, config: &Config) -> Result<(), Error> {
+            run_outerr_with(self, config.timeout, config.verbose)
+        }
+    }
+}
+
+/// Builds the AppImage described by `config` and returns its path, on a dry run
+/// the returned path is where the AppImage would have been written
+pub fn build_appimage(config: &Config) -> Result<PathBuf, Error> {
+    temp::start();
+    let mut steps = progress::Steps::new(config.timings);
+    let report = build(config, &mut steps);
+    steps.finish();
+    // Failed builds are cleaned up too
+    let cleaned = temp::clean_everything();
+    if config.timings {
+        eprint!("{}", steps.summary());
+    }
+
+    if let Some(path) = &config.json_report {
+        write_report(path, &report)?;
+    }
+    let output = report?.output;
+    cleaned?;
+
+    Ok(output)
+}
+
+fn build(config: &Config, steps: &mut progress::Steps) -> Result<Report, Error> {
+    use dialog::DialogBox;
+
+    if let Some(lang) = config.default_lang.as_deref().filter(|l| !appstream::is_language_tag(l)) {
+        return Err(Error::InvalidLanguage(lang.to_string()));
+    }
+    if let Some(id) = config.app_id.as_deref().filter(|id| !is_app_id(id)) {
+        return Err(Error::InvalidAppId(id.to_string()));
+    }
+    if let Some((key, _)) = config.x_keys.iter().find(|(key, _)| !is_extension_key(key)) {
+        return Err(Error::InvalidExtensionKey(key.clone()));
+    }
+    if let Some((name, _)) = config.env.iter().find(|(name, _)| !is_env_name(name)) {
+        return Err(Error::InvalidEnvName(name.clone()));
+    }
+    for (pattern, dest) in &config.bundle_extra {
+        check_bundle_extra(pattern, dest)?;
+    }
+    if let Some(info) = config.update_information.as_deref().filter(|i| !is_update_information(i)) {
+        return Err(Error::InvalidUpdateInformation(info.to_string()));
+    }
+    if let Some(kib) = config.block_size.filter(|kib| !is_block_size(*kib)) {
+        return Err(Error::InvalidBlockSize(kib));
+    }
+    if config.reproducible && config.signs() {
+        warn(config, "gpg signatures record when they were made, a signed AppImage isn't reproducible")?;
+    }
+    if config.signs() && config.packages() {
+        check_gpg(|name| cmd::app(name).is_some())?;
+    }
+
+    let conf = CliConf {
+        container_name: config.container.clone().unwrap_or_else(default_container_name),
+        ..CliConf::default()
+    };
+
+    steps.start("input");
+    let report = match PkgType::guess(&config.target, config)? {
+        PkgType::Deb(input) => {
+            steps.start("pkg2appimage");
+            let desktop = deb::desktop_info(&input).unwrap_or_else(|e| {
+                eprintln!("Couldn't read the desktop file of {}, pkg2appimage will look for it: {e}", input.display());
+                None
+            });
+            let binary = if desktop.is_none() { deb::main_binary(&input).unwrap_or_default() } else { None };
+            let desktop = match (desktop, &binary) {
+                (None, Some(binary)) => Some(binary_desktop(config, &conf, binary)?),
+                (desktop, _) => desktop,
+            };
+            if let Some(desktop) = &desktop {
+                check_categories(config, &desktop.categories)?;
+            }
+            let descriptor = deb_descriptor(&input, desktop.as_ref())?;
+            let name = config.name.clone().or_else(|| desktop.and_then(|d| d.name)).unwrap_or_else(|| descriptor.app.clone());
+            let version = config.app_version.clone().or_else(|| deb::version(&input));
+
+            let p_descriptor = write_descriptor(&input, &descriptor)?;
+            // pkg2appimage always fetches the packages from the sources
+            if !descriptor.ingredients.packages.is_empty() {
+                online(config, &format!("{} from {}", descriptor.ingredients.packages.join(", "), descriptor.ingredients.sources.join(", ")))?;
+            }
+            if !config.dry_run {
+                run_pkgtoappimage(config, &conf, &p_descriptor)?;
+            }
+
+            let produced = pkg2appimage_output(config.dry_run)?;
+            let output = if config.dry_run {
+                produced
+            } else {
+                let output = output_name(&name, version.as_deref());
+                check_output(&output, config.force, config.interactive)?;
+                fs::rename(produced, &output)?;
+                output
+            };
+            let executable = binary.map(|binary| PathBuf::from(binary.path));
+            Report { output, name: Some(name), version, executable, ..Default::default() }
+        }
+        PkgType::Yaml(input) => {
+            steps.start("pkg2appimage");
+            if !config.dry_run {
+                run_pkgtoappimage(config, &conf, &input)?;
+            }
+            Report { output: pkg2appimage_output(config.dry_run)?, ..Default::default() }
+        }
+        PkgType::Flatpak(input) => {
+            steps.start("appdir");
+            let input = stage_copy(&input)?;
+            let export = flatpak::read(&input)?;
+            flatpak::install(&input, &export, &config.env)?;
+
+            let name = config.name.clone().or_else(|| export.name.clone()).unwrap_or_else(|| export.id.clone());
+            let version = config.app_version.clone();
+            let output = output_name(&name, version.as_deref());
+            check_output(&output, config.force, config.interactive)?;
+
+            let mut report = Report {
+                output,
+                id: Some(export.id.clone()),
+                name: Some(name),
+                version,
+                executable: Some(input.join(export.target())),
+                ..Default::default()
+            };
+
+            if let Some(dir) = &config.export_metadata {
+                let metainfo = metainfo_files(&input);
+                let files: Vec<&Path> = std::iter::once(export.desktop_file.as_path()).chain(metainfo.iter().map(PathBuf::as_path)).collect();
+                export_metadata(&input, dir, &files)?;
+                report.output = dir.clone();
+                return Ok(report);
+            }
+            if let Some(dir) = &config.appdir_out {
+                write_appdir(&input, dir, config)?;
+                report.output = dir.clone();
+                return Ok(report);
+            }
+
+            if !config.dry_run {
+                steps.start("appimagetool");
+                report.sha256 = Some(run_appimagetool(config, &conf, &input, &report.output)?);
+            }
+            report
+        }
+        PkgType::Other(input) => {
+            steps.start("extract");
+            let actual_input = staged_appdir(config, prepare_input(config, input)?)?;
+            let lone_executable = lone_file(&actual_input);
+            // Before anything is added to the AppDir
+            let executable_guess = match &config.exec {
+                Some(exec) if actual_input.join(exec).is_file() => ExecutableGuess::Found(actual_input.join(exec)),
+                Some(exec) => return Err(Error::ExecutableNotFound(exec.clone())),
+                None => {
+                    check_preferences(config, &conf.prefer_executables)?;
+                    guess_executable(&actual_input, &conf)?
+                }
+            };
+
+            for link in archive::broken_symlinks(&actual_input)? {
+                warn(config, &format!("'{}' is dangling or points outside the AppDir", link.display()))?;
+            }
+
+            fn valid_icon(path: &Option<String>) -> Option<PathBuf> {
+                if let Some(icon) = path {
+                    let path = Path::new(icon).to_path_buf();
+                    if path.exists() {
+                        Some(path)
+                    }
+                    else { None}
+                }
+                else { None}
+            }
+
+            let mut default_icon = false;
+
+            // Due to how the pkg2appimagetool works we NEED an icon, that's why it isn't an
+            // option
+            steps.start("icon");
+            let mut icon_source = if let Some(icon) = valid_icon(&config.icon) {
+                IconSource::File(icon)
+            } else if let Some(url) = &config.icon_url {
+                IconSource::Url(url.clone())
+            } else {
+                let name = config.name.clone().unwrap_or_else(|| actual_input.file_name().unwrap_or_default().to_string_lossy().into_owned());
+                IconSource::detect(&actual_input, &name)
+            };
+            match &icon_source {
+                IconSource::File(icon) => {
+                    fs::copy(icon, actual_input.join("AppIcon.png"))?;
+                }
+                IconSource::Url(url) => {
+                    install_icon_from_url(url, &actual_input, |dir| {
+                        online(config, url)?;
+                        download_into(url, dir, config.download_timeout)
+                    })?;
+                }
+                IconSource::AppIcon(_) => {}
+                IconSource::Desktop(bundled) | IconSource::Image(bundled) => {
+                    let ext = if bundled.is_ext("svg") { "svg" } else { "png" };
+                    fs::copy(bundled, actual_input.join(format!("AppIcon.{ext}")))?;
+                }
+                IconSource::Exe(exe) => {
+                    default_icon = exe_icon(&conf, config, &actual_input, exe, |name| cmd::locate(name, &conf.tool_dirs()).is_some())?;
+                }
+                IconSource::Default => {
+                    write_default_icon(&actual_input, config)?;
+                    default_icon = true;
+                }
+            }
+            if default_icon {
+                icon_source = IconSource::Default;
+            }
+            let icon = "AppIcon".to_string();
+
+            let png_icon = actual_input.join("AppIcon.png");
+            if png_icon.exists() {
+                generate_icon_sizes(&png_icon, &actual_input, &ICON_SIZES)?;
+            }
+
+            let executable = match executable_guess {
+                ExecutableGuess::Found(file) => file,
+                ExecutableGuess::Ambiguous(exes) => {
+                    // dialog has no menus, so the candidates are numbered
+                    let question = format!(
+                        "Multiple exes where found, which one do you want to use? (use --exec to skip this)\n{}",
+                        exes.iter()
+                            .enumerate()
+                            .map(|(i, p)| format!("{}) {}", i + 1, display_relative(&actual_input, p)))
+                            .join("\n")
+                    );
+
+                    let mut exe_pb = None;
+                    while exe_pb.is_none() {
+                        let choice = dialog::Input::new(&question)
+                            .title("Which executable?")
+                            .default("1")
+                            .show()?
+                            .ok_or(Error::Cancelled)?;
+                        exe_pb = pick_candidate(&choice, &exes, &actual_input);
+
+                        if exe_pb.is_none() {
+                            dialog::Message::new(format!("Please enter a number from 1 to {}", exes.len()))
+                                .show()?
+                        }
+                    }
+                    exe_pb.unwrap().clone()
+                }
+            };
+            check_executable(config, &executable)?;
+            if is_elf(&executable)? {
+                match cmd::locate("patchelf", &conf.tool_dirs()) {
+                    Some(_) => check_elf_paths(config, &actual_input, &executable, &libs::Paths::read(|flag| libs::print(&executable, flag)))?,
+                    None if config.patch_interpreter => return Err(Error::ToolNotFound("patchelf".to_string())),
+                    None => {}
+                }
+            }
+            if lone_executable.is_some() || config.exec.is_some() {
+                mark_executable(&executable)?;
+            }
+
+            let shipped = shipped_metainfo(config, &actual_input)?.unwrap_or_default();
+            let shipped_name = shipped.name().filter(|_| config.name.is_none());
+            let shipped_summary = shipped.summary().filter(|_| config.summary.is_none());
+            let summary = config.summary.clone().or_else(|| shipped_summary.as_ref().map(|s| s.default.clone()));
+
+            let exe_name = match config.name.clone().or_else(|| shipped_name.as_ref().map(|n| n.default.clone())) {
+                Some(name) => name,
+                None => executable
+                    .file_stem()
+                    .ok_or_else(|| Error::ExecutableNotFound(executable.display().to_string()))?
+                    .to_string_lossy()
+                    .into_owned(),
+            };
+            let version = config.app_version.as_deref();
+            let output = output_name(&exe_name, version);
+            check_output(&output, config.force, config.interactive)?;
+            let default_lang = config.default_lang.as_deref();
+            // The shipped translations go first so the explicit ones win
+            let mut name_translations = shipped_name.as_ref().map(|n| n.translations.clone()).unwrap_or_default();
+            name_translations.extend(translations(&exe_name, &config.name_locale, default_lang));
+            let mut summary_translations = shipped_summary.map(|s| s.translations).unwrap_or_default();
+            summary_translations.extend(
+                summary
+                    .as_deref()
+                    .map(|summary| translations(summary, &config.summary_locale, default_lang))
+                    .unwrap_or_else(|| config.summary_locale.iter().cloned().collect()),
+            );
+
+            steps.start("appdir");
+            let categories = conf.categories(config, shipped.categories())?;
+            check_categories(config, &categories)?;
+            let mut entry = DesktopFile::new(exe_name, Some(icon), categories, config.terminal);
+            review_entry(config, &mut entry.file)?;
+            let terminal = entry.file.terminal;
+            entry.file.generic_name = config.generic_name.clone();
+            entry.file.comment = summary.clone();
+            entry.file.keywords = config.keywords.clone();
+            // Desktop files write locales as lang_COUNTRY instead of lang-COUNTRY
+            for (key, translated) in [("Name", &name_translations), ("Comment", &summary_translations)] {
+                if !translated.is_empty() && (key != "Comment" || summary.is_some()) {
+                    let translated = translated.iter().map(|(lang, text)| (lang.replace('-', "_"), text.clone())).collect();
+                    entry.file.translations.insert(key, translated);
+                }
+            }
+            entry.file.extra = extension_keys(config, &entry.file.name, version);
+            entry.file.no_display = config.no_display;
+            entry.file.hidden = config.hidden;
+            if let Some(try_exec) = &config.try_exec {
+                entry.file.try_exec = Some(try_exec.clone());
+            }
+
+            let id = config.app_id.clone().unwrap_or_else(|| app_id(&executable));
+            // The root folder has no name of its own
+            let whole_name = actual_input.file_name().map_or_else(|| entry.file.name.clone(), |n| n.to_string_lossy().into_owned());
+
+            if config.bundle_libs {
+                libs::bundle(&actual_input, &libs::dependencies(config, &executable)?)?;
+            }
+            for (pattern, dest) in &config.bundle_extra {
+                if bundle_extra(&actual_input, pattern, dest)? == 0 {
+                    warn(config, &format!("--bundle-extra '{pattern}' didn't match anything"))?;
+                }
+            }
+
+            if existing_apprun(&actual_input).is_some() {
+                eprintln!("Keeping the AppRun shipped with the app");
+                if !config.env.is_empty() {
+                    warn(config, "--env isn't applied to the AppRun shipped with the app")?;
+                }
+            } else if config.bundle_libs || !config.env.is_empty() || config.patch_interpreter {
+                // A copy in the root would change what `$ORIGIN` is
+                let script = apprun(executable.strip_prefix(&actual_input).unwrap_or(&executable), config.bundle_libs, &config.env);
+                let apprun = actual_input.join("AppRun");
+                fs::write(&apprun, script)?;
+                mark_executable(&apprun)?;
+            } else {
+                std::fs::copy(&executable, actual_input.join("AppRun"))?;
+            }
+
+   
+            // Make appstream
+            // usr/share/metainfo/myapp.appdata.xml
+            let summary = summary.unwrap_or_else(|| "TODO!TODO!".to_string());
+            let description = match shipped.description {
+                Some(shipped) if config.description.is_none() && config.description_file.is_none() => shipped,
+                _ => Description::parse(&description(config)?),
+            };
+            const NAME_LIMIT: usize = 15;
+
+            let appstream = AppStream {
+                component: AppStreamComponent {
+                    ctype: if terminal {
+                        ComponentType::ConsoleApplication
+                    } else {
+                        ComponentType::DesktopApplication
+                    },
+                    id: id.clone(),
+                    metadata_license: config.metadata_license.clone(),
+                    project_license: project_license(config, &actual_input)?,
+                    name: LocalizedString::new(config.name.clone().or_else(|| shipped_name.map(|n| n.default)).unwrap_or_else(|| whole_name.chars().take(NAME_LIMIT).collect()))
+                        .with_translations(name_translations),
+                    summary: LocalizedString::new(summary).with_translations(summary_translations),
+                    description,
+                    launchable: Launchable::desktop(&id),
+                    url: Some(Url{ctype: appstream::UrlType::Homepage, data: "https://github.com/sheosi/to_appimage".to_string()}),
+                    keywords: Keywords { keyword: config.keywords.clone() },
+                    project_group: config.project_group.clone(),
+                    update_contact: config.update_contact.clone(),
+                    screenshots: Screenshots{screenshot: vec![Screenshot{ctype: ScreenshotType::Default, image: "https://placehold.co/700x400.png".to_string()}]},
+                    provides: if terminal {
+                        Provides::console(&id, &executable.file_name().unwrap_or_default().to_string_lossy())
+                    } else {
+                        Provides::desktop(&id)
+                    },
+                    recommends: SupportsControl { control: config.supports.clone() },
+                    releases: Releases::current(version),
+                    custom: Custom {
+                        value: if default_icon {
+                            vec![CustomValue { key: "to_appimage::default-icon".to_string(), value: "true".to_string() }]
+                        } else {
+                            vec![]
+                        },
+                    },
+                    content_rating: ContentRating::from_profile(rating_profile(config)?),
+                },
+            };
+
+            // The shipped metainfo is merged into the generated one, two
+            // components would clash, but a folder of the user's keeps it
+            let generated = actual_input.join(format!("usr/share/metainfo/{}.appdata.xml", appstream.component.id));
+            for shipped in metainfo_files(&actual_input).into_iter().filter(|f| *f != generated) {
+                if temp::contains(&shipped) {
+                    fs::remove_file(shipped)?;
+                } else {
+                    warn(config, &format!("'{}' is left in place, the AppImage will have two AppStream components", shipped.display()))?;
+                }
+            }
+            let (desktop_file, metainfo) = install_metadata(&actual_input, &entry, &appstream)?;
+
+            if !config.no_appstream_validate {
+                if which::which("appstreamcli").is_err() {
+                    warn(config, "appstreamcli isn't installed so the AppStream metadata wasn't validated")?;
+                }
+                appstream::validate(&metainfo)?;
+            }
+
+            let mut report = Report {
+                output: output.clone(),
+                id: Some(id.clone()),
+                name: Some(entry.file.name.clone()),
+                version: version.map(str::to_string),
+                license: Some(appstream.component.project_license.clone()),
+                executable: Some(executable.clone()),
+                icon: Some(icon_source),
+                sha256: None,
+            };
+
+            if let Some(dir) = &config.export_metadata {
+                export_metadata(&actual_input, dir, &[&desktop_file, &metainfo])?;
+                report.output = dir.clone();
+                return Ok(report);
+            }
+            if let Some(dir) = &config.appdir_out {
+                write_appdir(&actual_input, dir, config)?;
+                report.output = dir.clone();
+                return Ok(report);
+            }
+
+            if !config.dry_run {
+                steps.start("appimagetool");
+                report.sha256 = Some(run_appimagetool(config, &conf, &actual_input, &output)?);
+            }
+
+            report
+        }
+    };
+
+    Ok(report)
+}
+
+
+/// Packs the AppDir, downloading appimagetool if it isn't installed, returns
+/// the AppImage's SHA-256
+fn run_appimagetool(config: &Config, conf: &CliConf, appdir: &Path, output: &Path) -> Result<String, Error> {
+    let mut appimagetool = match cmd::app_named(&conf.tool_names("appimagetool"), &conf.tool_dirs()) {
+        Some(appimagetool) => appimagetool,
+        None => cmd::cached_app("appimagetool.appimage", &APPIMAGETOOL_LINKSET, config.download_timeout, config.offline)?,
+    };
+    let appdir = if config.reproducible {
+        let epoch = source_date_epoch();
+        appimagetool.env("SOURCE_DATE_EPOCH", epoch.to_string());
+        normalized_appdir(appdir, epoch)?
+    } else {
+        appdir.to_path_buf()
+    };
+    appimagetool.args(appimagetool_args(config, &appdir, output));
+
+    (&mut appimagetool).run_outerr(config)?;
+
+    let summary = summarize(output, config.write_checksum)?;
+    // stdout may be carrying the JSON report
+    eprintln!("{}: {} bytes, SHA-256 {}", output.display(), summary.size, summary.sha256);
+    Ok(summary.sha256)
+}
+
+/// An entry of a batch manifest, anything not set is taken from the base
+/// `Config`
+#[derive(Debug, Deserialize)]
+pub struct ManifestEntry {
+    pub target: String,
+    pub name: Option<String>,
+    pub categories: Option<Vec<String>>,
+    pub icon: Option<String>,
+}
+
+impl ManifestEntry {
+    fn apply(self, base: &Config) -> Config {
+        Config {
+            name: self.name.or_else(|| base.name.clone()),
+            categories: self.categories.unwrap_or_else(|| base.categories.clone()),
+            icon: self.icon.or_else(|| base.icon.clone()),
+            target: self.target,
+            ..base.clone()
+        }
+    }
+}
+
+/// Reads a manifest, either a YAML list of entries (for `.yaml` and `.yml`
+/// files) or one target per line, `-` reads the lines from stdin
+pub fn read_manifest(path: &Path) -> Result<Vec<ManifestEntry>, Error> {
+    if path.is_ext("yaml") || path.is_ext("yml") {
+        return Ok(serde_yaml::from_reader(File::open(path)?)?);
+    }
+
+    let contents = if path == Path::new("-") {
+        std::io::read_to_string(std::io::stdin())?
+    } else {
+        fs::read_to_string(path)?
+    };
+
+    Ok(contents
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(|target| ManifestEntry {
+            target: target.to_string(),
+            name: None,
+            categories: None,
+            icon: None,
+        })
+        .collect())
+}
+
+/// Builds the entries one after another, a failure doesn't stop the rest,
+/// results are in the same order as the entries. Builds share the temporary
+/// folder, the working directory and the dialogs, so they can't run at once
+pub fn build_batch(base: &Config, entries: Vec<ManifestEntry>) -> Vec<(String, Result<PathBuf, Error>)> {
+    temp::start();
+    let results: Vec<_> = entries
+        .into_iter()
+        .map(|entry| {
+            let config = entry.apply(base);
+            let mut steps = progress::Steps::new(config.timings);
+            let res = build(&config, &mut steps).map(|report| report.output);
+            steps.finish();
+            (config.target, res)
+        })
+        .collect();
+
+    // Cleaned once at the end, like a single build does
+    if let Err(e) = temp::clean_everything() {
+        eprintln!("Couldn't clean temporary files: {e}");
+    }
+
+    results
+}
+
+/// What an AppImage or AppDir says about itself
+#[derive(Debug, Default)]
+pub struct Inspection {
+    pub desktop_entry: Option<String>,
+    pub metainfo: Option<String>,
+    pub license: Option<License>,
+}
+
+fn metainfo_files(appdir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(appdir.join("usr/share/metainfo")) else {
+        return Vec::new();
+    };
+
+    entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.is_ext("xml"))
+        .sorted()
+        .collect()
+}
+
+fn inspect_appdir(appdir: &Path) -> Result<Inspection, Error> {
+    Ok(Inspection {
+        desktop_entry: look_for_ext(appdir, "desktop").map(fs::read_to_string).transpose()?,
+        metainfo: metainfo_files(appdir).first().map(fs::read_to_string).transpose()?,
+        license: License::locate(appdir).ok(),
+    })
+}
+
+/// Reads the metadata of an AppImage, which is extracted first, or of an
+/// AppDir
+pub fn inspect(target: &Path) -> Result<Inspection, Error> {
+    if target.is_dir() {
+        return inspect_appdir(target);
+    }
+
+    let appimage = fs::canonicalize(target)?;
+    temp::start();
+    let tmp = temp::try_create("inspect")?;
+    let res = cmd::run_with(Command::new(appimage).arg("--appimage-extract").current_dir(&tmp), cmd::DEFAULT_TIMEOUT)
+        .and_then(|_| inspect_appdir(&tmp.join("squashfs-root")));
+    temp::clean_everything()?;
+
+    res
+}
+
+/// Removes the temporary folders runs that didn't finish left behind, returns
+/// how many bytes were freed
+pub fn clean() -> Result<u64, Error> {
+    Ok(temp::clean_stale()?)
+}
+
+/// Checks the desktop file and the AppStream metadata of an AppDir with
+/// `desktop-file-validate` and `appstreamcli`, missing tools are skipped
+pub fn validate(appdir: &Path) -> Result<(), Error> {
+    let Some(desktop_file) = look_for_ext(appdir, "desktop") else {
+        return Err(Error::InvalidDesktopFile(format!("there's no desktop file in {}", appdir.display())));
+    };
+
+    if let Some(mut validator) = cmd::app("desktop-file-validate") {
+        let out = validator.arg(&desktop_file).output()?;
+        if !out.status.success() {
+            return Err(Error::InvalidDesktopFile(
+                String::from_utf8_lossy(&out.stdout).into_owned() + &String::from_utf8_lossy(&out.stderr),
+            ));
+        }
+    }
+
+    for metainfo in metainfo_files(appdir) {
+        appstream::validate(&metainfo)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use std::path::Path;
+
+    use crate::{
+        app_id, appimagetool_args, apprun, appstream, archive, binary_desktop, bundle_extra,
+        bundled_desktop_icon, cache, check_bundle_extra, check_categories, check_elf_paths,
+        check_executable, check_gpg, check_output, cmd, deb, deb_descriptor, desktop_entry, exe_icon,
+        extension_keys, generate_icon_sizes, github, guess_executable, http, install_icon_from_url,
+        install_metadata, is_app_id, is_block_size, is_env_name, is_extension_key,
+        is_update_information, libs, licensing, lone_file, look_for_ext, mark_executable,
+        mksquashfs_opts, normalized_appdir, output_name, pick_candidate, preferred_executable,
+        prepare_input, progress, project_license, review_entry, staged_appdir, summarize, temp,
+        top_level, write_default_icon, write_descriptor, CliConf, CliKind, Compression, Config,
+        DesktopFile, Error, ExecutableGuess, IconSource, PkgType, Summary, TopLevel,
+    };
+
+    /// Removes what the test's builds staged in its temp root, even if the
+    /// test fails
+    struct Staged;
+
+    impl Drop for Staged {
+        fn drop(&mut self) {
+            let _ = temp::clean_everything();
+        }
+    }
+
+    #[test]
+    fn desktop_file_in_both_paths() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("desktop");
+        fs::create_dir_all(&dir).unwrap();
+
+        let entry = DesktopFile::new("app".to_string(), Some("AppIcon".to_string()), vec!["Utility".to_string()], false);
+        entry.write(&dir, "com.to_appimage.app").unwrap();
+
+        let root = fs::read_to_string(dir.join("com.to_appimage.app.desktop")).unwrap();
+        let installed =
+            fs::read_to_string(dir.join("usr/share/applications/com.to_appimage.app.desktop")).unwrap();
+
+        assert_eq!(root, installed);
+    }
+
+    #[test]
+    fn app_id_with_spaces() {
+        assert_eq!(app_id(Path::new("/tmp/My Cool App")), "com.to_appimage.my-cool-app");
+    }
+
+    #[test]
+    fn app_id_is_validated() {
+        assert!(is_app_id("com.example.Foo"));
+        assert!(is_app_id("org.example.my_app-2"));
+        assert!(is_app_id(&app_id(Path::new("/tmp/My Cool App"))));
+        assert!(!is_app_id("Foo"));
+        assert!(!is_app_id("com..Foo"));
+        assert!(!is_app_id("com.example.2fast"));
+        assert!(!is_app_id("com.example.Foo App"));
+    }
+
+    #[test]
+    fn app_id_with_exe() {
+        assert_eq!(app_id(Path::new("/tmp/Setup.exe")), "com.to_appimage.setup");
+    }
+
+    #[test]
+    fn yml_is_descriptor() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("yml");
+        fs::create_dir_all(&dir).unwrap();
+        let recipe = dir.join("recipe.yml");
+        fs::write(&recipe, "app: test").unwrap();
+
+        let kind = PkgType::guess_local(recipe.to_str().unwrap()).unwrap();
+
+        assert!(matches!(kind, PkgType::Yaml(_)));
+    }
+
+    fn guess_file(name: &str, contents: &[u8]) -> PkgType {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join(format!("guess_{name}"));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join(name);
+        fs::write(&file, contents).unwrap();
+
+        let kind = PkgType::guess_local(file.to_str().unwrap()).unwrap();
+        kind
+    }
+
+    #[test]
+    fn uppercase_deb() {
+        assert!(matches!(guess_file("app.DEB", b""), PkgType::Deb(_)));
+    }
+
+    #[test]
+    fn deb_by_magic() {
+        assert!(matches!(guess_file("download", b"!<arch>\ndebian-binary   1342"), PkgType::Deb(_)));
+    }
+
+    #[test]
+    fn yaml_by_contents() {
+        assert!(matches!(
+            guess_file("recipe", b"app: test\ningredients:\n  packages:\n    - test\n"),
+            PkgType::Yaml(_)
+        ));
+    }
+
+    #[test]
+    fn submodule_message_is_kept() {
+        let message = licensing::Error::NoLicenseFile.to_string();
+        let error: Error = licensing::Error::NoLicenseFile.into();
+
+        assert!(error.to_string().contains(&message));
+    }
+
+    #[test]
+    fn second_download_hits_cache() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("cache");
+        let url = "https://example.com/app.deb";
+        let downloads = std::cell::Cell::new(0);
+        let stub = |d: &std::path::Path| {
+            downloads.set(downloads.get() + 1);
+            fs::write(d.join("app.deb"), "contents").map_err(Error::from)
+        };
+
+        let first = cache::fetch(&dir, url, None, stub).unwrap();
+        let second = cache::fetch(&dir, url, None, stub).unwrap();
+
+        assert_eq!(first, second);
+        assert_eq!(downloads.get(), 1);
+    }
+
+    #[test]
+    fn version_and_generic_name() {
+        let mut entry = DesktopFile::new("app".to_string(), None, vec!["Utility".to_string()], false);
+        entry.file.generic_name = Some("Calculator".to_string());
+        let desktop = desktop_entry::to_string(&entry).unwrap();
+
+        assert!(desktop.contains("\nVersion=1.5\n"));
+        assert!(desktop.contains("\nGenericName=Calculator\n"));
+
+        entry.file.generic_name = None;
+        assert!(!desktop_entry::to_string(&entry).unwrap().contains("GenericName"));
+    }
+
+    #[test]
+    fn keywords() {
+        let mut entry = DesktopFile::new("app".to_string(), None, vec!["Utility".to_string()], false);
+        assert!(!desktop_entry::to_string(&entry).unwrap().contains("Keywords"));
+
+        entry.file.keywords = vec!["calculator".to_string(), "math".to_string()];
+        assert!(desktop_entry::to_string(&entry).unwrap().contains("\nKeywords=calculator;math;\n"));
+    }
+
+    #[test]
+    fn no_display() {
+        let mut entry = DesktopFile::new("app".to_string(), None, vec!["Utility".to_string()], false);
+        let desktop = desktop_entry::to_string(&entry).unwrap();
+        assert!(!desktop.contains("NoDisplay"));
+        assert!(!desktop.contains("Hidden"));
+        assert!(!desktop.contains("Terminal"));
+
+        entry.file.no_display = true;
+        assert!(desktop_entry::to_string(&entry).unwrap().contains("\nNoDisplay=true\n"));
+    }
+
+    #[test]
+    fn try_exec() {
+        let mut entry = DesktopFile::new("app".to_string(), None, vec!["Utility".to_string()], false);
+        assert!(!desktop_entry::to_string(&entry).unwrap().contains("TryExec="));
+
+        entry.file.try_exec = Some("app-helper".to_string());
+        assert!(desktop_entry::to_string(&entry).unwrap().contains("\nTryExec=app-helper\n"));
+    }
+
+    #[test]
+    fn existing_output_is_kept() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("output");
+        fs::create_dir_all(&dir).unwrap();
+        let output = dir.join("app-x86_64.AppImage");
+        fs::write(&output, "previous").unwrap();
+
+        let res = check_output(&output, false, false);
+        let contents = fs::read_to_string(&output).unwrap();
+        let forced = check_output(&output, true, false);
+
+        assert!(matches!(res, Err(Error::OutputExists(_))));
+        assert_eq!(contents, "previous");
+        assert!(forced.is_ok());
+    }
+
+    #[test]
+    fn every_icon_size() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("icons");
+        fs::create_dir_all(&dir).unwrap();
+        let icon = dir.join("AppIcon.png");
+        image::RgbaImage::from_pixel(512, 512, image::Rgba([255, 0, 0, 255])).save(&icon).unwrap();
+
+        let sizes = [16, 32, 48, 64, 128, 256];
+        let written = generate_icon_sizes(&icon, &dir, &sizes).unwrap();
+
+        assert_eq!(written.len(), sizes.len());
+        for size in sizes {
+            let path = dir.join(format!("usr/share/icons/hicolor/{size}x{size}/apps/AppIcon.png"));
+            assert_eq!(image::image_dimensions(&path).unwrap(), (size, size));
+        }
+    }
+
+    fn is_executable(path: &std::path::Path) -> bool {
+        use std::os::unix::fs::PermissionsExt;
+        fs::metadata(path).unwrap().permissions().mode() & 0o111 != 0
+    }
+
+    #[test]
+    fn extract_zip() {
+        use std::io::Write;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("zip");
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("app.zip");
+
+        let mut zip = zip::ZipWriter::new(fs::File::create(&input).unwrap());
+        let options = zip::write::SimpleFileOptions::default().unix_permissions(0o755);
+        zip.start_file("app/run.sh", options).unwrap();
+        zip.write_all(b"#!/bin/sh\n").unwrap();
+        zip.finish().unwrap();
+
+        let out = dir.join("out");
+        fs::create_dir_all(&out).unwrap();
+        archive::unarchive(&input, &out, &archive::Limits::default()).unwrap();
+
+        let contents = fs::read_to_string(out.join("app/run.sh")).unwrap();
+        let executable = is_executable(&out.join("app/run.sh"));
+
+        assert_eq!(contents, "#!/bin/sh\n");
+        assert!(executable);
+    }
+
+    #[test]
+    fn extract_tar_gz() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("targz");
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("app.tar.gz");
+
+        let data = b"#!/bin/sh\n";
+        let encoder = flate2::write::GzEncoder::new(fs::File::create(&input).unwrap(), flate2::Compression::default());
+        let mut builder = tar::Builder::new(encoder);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o755);
+        header.set_cksum();
+        builder.append_data(&mut header, "app/run.sh", &data[..]).unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+
+        let out = dir.join("out");
+        fs::create_dir_all(&out).unwrap();
+        archive::unarchive(&input, &out, &archive::Limits::default()).unwrap();
+
+        let contents = fs::read_to_string(out.join("app/run.sh")).unwrap();
+        let executable = is_executable(&out.join("app/run.sh"));
+
+        assert_eq!(contents, "#!/bin/sh\n");
+        assert!(executable);
+    }
+
+    #[test]
+    fn extract_symlinks() {
+        use std::io::Write;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("symlinks");
+        fs::create_dir_all(&dir).unwrap();
+
+        let tar_input = dir.join("app.tar");
+        let mut builder = tar::Builder::new(fs::File::create(&tar_input).unwrap());
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Symlink);
+        header.set_size(0);
+        builder.append_link(&mut header, "app/libfoo.so", "libfoo.so.1").unwrap();
+        builder.into_inner().unwrap();
+
+        let zip_input = dir.join("app.zip");
+        let mut zip = zip::ZipWriter::new(fs::File::create(&zip_input).unwrap());
+        let options = zip::write::SimpleFileOptions::default();
+        zip.start_file("app/libfoo.so.1", options).unwrap();
+        zip.write_all(b"library").unwrap();
+        zip.add_symlink("app/libfoo.so", "libfoo.so.1", options).unwrap();
+        zip.finish().unwrap();
+
+        let mut results = Vec::new();
+        for (input, out) in [(&tar_input, dir.join("tar")), (&zip_input, dir.join("zip"))] {
+            fs::create_dir_all(&out).unwrap();
+            archive::unarchive(input, &out, &archive::Limits::default()).unwrap();
+            let link = out.join("app/libfoo.so");
+            results.push((
+                fs::symlink_metadata(&link).unwrap().file_type().is_symlink(),
+                fs::read_link(&link).unwrap(),
+            ));
+        }
+        let broken = archive::broken_symlinks(&dir.join("zip")).unwrap();
+
+        for (is_symlink, target) in results {
+            assert!(is_symlink);
+            assert_eq!(target, std::path::Path::new("libfoo.so.1"));
+        }
+        assert!(broken.is_empty());
+    }
+
+    #[test]
+    fn zip_slip_is_refused() {
+        use std::io::Write;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("zipslip");
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("evil.zip");
+
+        let mut zip = zip::ZipWriter::new(fs::File::create(&input).unwrap());
+        zip.start_file("../evil.txt", zip::write::SimpleFileOptions::default()).unwrap();
+        zip.write_all(b"evil").unwrap();
+        zip.finish().unwrap();
+
+        let out = dir.join("out");
+        fs::create_dir_all(&out).unwrap();
+        let res = archive::unarchive(&input, &out, &archive::Limits::default());
+        let escaped = dir.join("evil.txt").exists();
+
+        assert!(matches!(res, Err(Error::UnsafeArchiveEntry { .. })));
+        assert!(!escaped);
+    }
+
+    #[test]
+    fn zip_symlinks_are_not_written_through() {
+        use std::io::Write;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("zipslip_links");
+        fs::create_dir_all(&dir).unwrap();
+        let options = zip::write::SimpleFileOptions::default();
+
+        let escaping = dir.join("escaping.zip");
+        let mut zip = zip::ZipWriter::new(fs::File::create(&escaping).unwrap());
+        zip.add_symlink("app/a", "../../evil.txt", options).unwrap();
+        zip.finish().unwrap();
+
+        // The same name twice, first as a link and then as a file
+        let overwriting = dir.join("overwriting.zip");
+        let mut zip = zip::ZipWriter::new(fs::File::create(&overwriting).unwrap());
+        zip.add_symlink("app/a", "b", options).unwrap();
+        zip.start_file("app/./a", options).unwrap();
+        zip.write_all(b"evil").unwrap();
+        zip.finish().unwrap();
+
+        let mut results = Vec::new();
+        for (input, out) in [(&escaping, dir.join("escaping")), (&overwriting, dir.join("overwriting"))] {
+            fs::create_dir_all(&out).unwrap();
+            results.push(archive::unarchive(input, &out, &archive::Limits::default()));
+        }
+        let escaped = dir.join("evil.txt").exists();
+        let written_through = dir.join("overwriting/app/b").exists();
+
+        for res in results {
+            assert!(matches!(res, Err(Error::UnsafeArchiveEntry { .. })), "{res:?}");
+        }
+        assert!(!escaped && !written_through);
+    }
+
+    #[test]
+    fn icon_from_url() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("icon_url");
+        fs::create_dir_all(&dir).unwrap();
+
+        let stub = |d: &std::path::Path| {
+            image::RgbaImage::from_pixel(64, 64, image::Rgba([0, 0, 255, 255]))
+                .save_with_format(d.join("icon"), image::ImageFormat::Png)
+                .map_err(Error::from)
+        };
+        install_icon_from_url("https://example.com/icon", &dir, stub).unwrap();
+
+        let dimensions = image::image_dimensions(dir.join("AppIcon.png")).unwrap();
+
+        assert_eq!(dimensions, (256, 256));
+    }
+
+    #[test]
+    fn strict_refuses_default_icon() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("strict_icon");
+        let config = Config { strict: true, ..Config::new("app") };
+
+        let res = write_default_icon(&dir, &config);
+
+        assert!(matches!(res, Err(Error::DefaultIcon)));
+        assert!(!dir.join("AppIcon.svg").exists());
+    }
+
+    #[test]
+    fn icon_from_bundled_desktop() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("bundled_icon");
+        let apps = dir.join("usr/share/applications");
+        fs::create_dir_all(&apps).unwrap();
+        fs::write(apps.join("myapp.desktop"), "[Desktop Entry]\nName=My App\nIcon=myapp\n").unwrap();
+
+        for size in [32, 128] {
+            let icons = dir.join(format!("usr/share/icons/hicolor/{size}x{size}/apps"));
+            fs::create_dir_all(&icons).unwrap();
+            fs::write(icons.join("myapp.png"), b"").unwrap();
+        }
+
+        let icon = bundled_desktop_icon(&dir);
+
+        assert_eq!(icon, Some(dir.join("usr/share/icons/hicolor/128x128/apps/myapp.png")));
+    }
+
+    #[test]
+    fn preferred_executable_is_picked() {
+        let exes = ["/app/foo", "/app/foo-helper", "/app/crash-reporter"].map(std::path::PathBuf::from);
+        let preferences = ["bar".to_string(), "foo-*".to_string(), "foo".to_string()];
+
+        assert_eq!(preferred_executable(&exes, &preferences), Some("/app/foo-helper".into()));
+        assert_eq!(preferred_executable(&exes, &["bar".to_string()]), None);
+    }
+
+    #[test]
+    fn summary_of_output() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("summary");
+        fs::create_dir_all(&dir).unwrap();
+
+        // Stands for what appimagetool would have produced
+        let output = dir.join("app-x86_64.AppImage");
+        fs::write(&output, b"abc").unwrap();
+
+        let summary = summarize(&output, true).unwrap();
+        let checksum_file = fs::read_to_string(dir.join("app-x86_64.AppImage.sha256")).unwrap();
+
+        let sha256 = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        assert_eq!(summary, Summary { size: 3, sha256: sha256.to_string() });
+        assert_eq!(checksum_file, format!("{sha256}  app-x86_64.AppImage\n"));
+    }
+
+    #[test]
+    fn missing_container() {
+        let conf = CliConf { container_name: "fedora-toolbox-40".to_string(), ..CliConf::default() };
+
+        let res = conf.ensure_container(|_| Ok(false));
+
+        assert!(matches!(&res, Err(Error::ContainerNotFound(name)) if name == "fedora-toolbox-40"));
+        assert!(res.unwrap_err().to_string().contains("fedora-toolbox-40"));
+        assert!(conf.ensure_container(|_| Ok(true)).is_ok());
+    }
+
+    #[test]
+    fn categories_from_preset() {
+        let conf = CliConf::default();
+        let dev = Config { preset: Some("dev".to_string()), ..Config::new("app") };
+        assert_eq!(conf.categories(&dev, &[]).unwrap(), vec!["Development", "IDE"]);
+
+        let combined = Config { categories: vec!["IDE".to_string(), "Utility".to_string()], ..dev };
+        assert_eq!(conf.categories(&combined, &["Office".to_string()]).unwrap(), vec!["Development", "IDE", "Utility"]);
+
+        assert_eq!(conf.categories(&Config::new("app"), &[]).unwrap(), vec!["Utility"]);
+        assert_eq!(conf.categories(&Config::new("app"), &["Office".to_string()]).unwrap(), vec!["Office"]);
+    }
+
+    #[test]
+    fn download_progress() {
+        let data = vec![7u8; 200_000];
+        let mut copied = Vec::new();
+        let mut reports = Vec::new();
+
+        let total = http::copy_with_progress(data.as_slice(), &mut copied, Some(200_000), |done, total| {
+            reports.push((done, total))
+        })
+        .unwrap();
+
+        assert_eq!(total, 200_000);
+        assert_eq!(copied, data);
+        assert!(reports.len() > 1);
+        assert!(reports.windows(2).all(|w| w[0].0 < w[1].0));
+        assert_eq!(reports.last(), Some(&(200_000, Some(200_000))));
+    }
+
+    #[test]
+    fn download_file_name() {
+        assert_eq!(http::file_name("https://example.com/a/app.tar.gz?raw=1"), "app.tar.gz");
+        assert_eq!(http::file_name("https://example.com/a/"), "a");
+    }
+
+    #[cfg(feature = "net")]
+    #[test]
+    fn download_from_local_server() {
+        use std::io::{BufRead, BufReader, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            for stream in listener.incoming().take(2) {
+                let mut stream = stream.unwrap();
+                let mut request_line = String::new();
+                let mut reader = BufReader::new(&stream);
+                reader.read_line(&mut request_line).unwrap();
+                // Skip the headers
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+
+                let response = if request_line.starts_with("GET /old ") {
+                    "HTTP/1.1 302 Found\r\nLocation: /app.sh\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                } else {
+                    "HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello"
+                };
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("http");
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("app.sh");
+
+        let res = http::download(&format!("http://{addr}/old"), &file, Some(std::time::Duration::from_secs(10)), |_, _| {});
+        let contents = fs::read_to_string(&file);
+        server.join().unwrap();
+
+        res.unwrap();
+        assert_eq!(contents.unwrap(), "hello");
+    }
+
+    #[test]
+    fn failed_command_output_is_kept() {
+        let res = cmd::run_outerr_with(
+            std::process::Command::new("sh").args(["-c", "echo 'Desktop file is missing'; echo 'mksquashfs failed' >&2; exit 1"]),
+            std::time::Duration::from_secs(10),
+            false,
+        );
+
+        assert!(matches!(
+            res,
+            Err(Error::CommandFailed { program, output })
+                if program == "sh" && output == "stdout:\nDesktop file is missing\nstderr:\nmksquashfs failed"
+        ));
+    }
+
+    #[test]
+    fn command_timeout() {
+        let start = std::time::Instant::now();
+        let res = cmd::run_with(std::process::Command::new("sleep").arg("5"), std::time::Duration::from_millis(200));
+
+        assert!(matches!(res, Err(Error::CommandTimedOut { program }) if program == "sleep"));
+        assert!(start.elapsed() < std::time::Duration::from_secs(5));
+    }
+
+    #[test]
+    fn timeout_comes_from_the_config() {
+        use cmd::RunExt;
+
+        let config = Config { timeout: std::time::Duration::from_millis(200), ..Config::new("app") };
+        let res = (&mut std::process::Command::new("sleep").arg("5")).run(&config);
+
+        assert!(matches!(res, Err(Error::CommandTimedOut { .. })));
+    }
+
+    #[test]
+    fn single_file_archive() {
+        let _staged = Staged;
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("single_file");
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("single_file.tar.gz");
+
+        let data = b"\x7fELF";
+        let encoder = flate2::write::GzEncoder::new(fs::File::create(&input).unwrap(), flate2::Compression::default());
+        let mut builder = tar::Builder::new(encoder);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, "mytool.bin", &data[..]).unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+
+        let appdir = prepare_input(&Config::new("app"), input).unwrap();
+        let executable = lone_file(&appdir);
+        let is_dir = appdir.is_dir();
+
+        assert!(is_dir);
+        assert_eq!(executable, Some(appdir.join("mytool.bin")));
+    }
+
+    #[test]
+    fn archive_inside_archive() {
+        use std::io::Write;
+
+        let _staged = Staged;
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("double");
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("double.zip");
+
+        let data = b"#!/bin/sh\n";
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default()));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o755);
+        header.set_cksum();
+        builder.append_data(&mut header, "app/run.sh", &data[..]).unwrap();
+        let tarball = builder.into_inner().unwrap().finish().unwrap();
+
+        let mut zip = zip::ZipWriter::new(fs::File::create(&input).unwrap());
+        zip.start_file("double.tar.gz", zip::write::SimpleFileOptions::default()).unwrap();
+        zip.write_all(&tarball).unwrap();
+        zip.finish().unwrap();
+
+        let appdir = prepare_input(&Config::new("app"), input).unwrap();
+        let contents = fs::read_to_string(appdir.join("run.sh"));
+
+        assert!(appdir.ends_with("app"));
+        assert_eq!(contents.unwrap(), "#!/bin/sh\n");
+    }
+
+    #[test]
+    fn extraction_limit() {
+        use std::io::Write;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("limit");
+        fs::create_dir_all(&dir).unwrap();
+        let data = [0u8; 1024];
+        let limits = archive::Limits { max_size: 100, ..Default::default() };
+
+        let zip_input = dir.join("big.zip");
+        let mut zip = zip::ZipWriter::new(fs::File::create(&zip_input).unwrap());
+        zip.start_file("big.bin", zip::write::SimpleFileOptions::default()).unwrap();
+        zip.write_all(&data).unwrap();
+        zip.finish().unwrap();
+
+        let tar_input = dir.join("big.tar");
+        let mut builder = tar::Builder::new(fs::File::create(&tar_input).unwrap());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_cksum();
+        builder.append_data(&mut header, "big.bin", &data[..]).unwrap();
+        builder.finish().unwrap();
+
+        let mut results = Vec::new();
+        for (input, out) in [(&zip_input, dir.join("zip")), (&tar_input, dir.join("tar"))] {
+            fs::create_dir_all(&out).unwrap();
+            results.push(archive::unarchive(input, &out, &limits));
+        }
+
+        for res in results {
+            assert!(matches!(res, Err(Error::ArchiveTooLarge(_))));
+        }
+    }
+
+    #[test]
+    fn long_zip_link_is_refused() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("long_link");
+        let (out, input) = (dir.join("out"), dir.join("link.zip"));
+        fs::create_dir_all(&out).unwrap();
+
+        let mut zip = zip::ZipWriter::new(fs::File::create(&input).unwrap());
+        zip.add_symlink("link", "a/".repeat(4096), zip::write::SimpleFileOptions::default()).unwrap();
+        zip.finish().unwrap();
+
+        let res = archive::unarchive(&input, &out, &archive::Limits::default());
+        let created = fs::symlink_metadata(out.join("link")).is_ok();
+
+        assert!(matches!(res, Err(Error::UnsafeArchiveEntry { .. })), "{res:?}");
+        assert!(!created);
+    }
+
+    #[test]
+    fn external_tar_is_measured_first() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("limit_external");
+        let (out, input) = (dir.join("out"), dir.join("big.tar.bz2"));
+        fs::create_dir_all(&out).unwrap();
+        fs::write(dir.join("big.bin"), vec![0u8; 64 * 1024]).unwrap();
+        let packed = std::process::Command::new("tar").arg("-cjf").arg(&input).arg("-C").arg(&dir).arg("big.bin").status().unwrap();
+
+        let limits = archive::Limits { max_size: 1024, ..Default::default() };
+        let res = archive::unarchive(&input, &out, &limits);
+        let written = fs::read_dir(&out).unwrap().count();
+
+        assert!(packed.success());
+        assert!(matches!(res, Err(Error::ArchiveTooLarge(_))));
+        assert_eq!(written, 0);
+    }
+
+    #[test]
+    fn appimagetool_compression() {
+        let config = Config {
+            compression: Some("xz".parse().unwrap()),
+            ..Config::new("app")
+        };
+
+        let args = appimagetool_args(&config, Path::new("app"), Path::new("app-x86_64.AppImage"));
+        assert_eq!(args, ["--comp", "xz", "app", "app-x86_64.AppImage"]);
+        assert_eq!(appimagetool_args(&Config::new("app"), Path::new("app"), Path::new("app.AppImage")), ["app", "app.AppImage"]);
+        assert!("lzma".parse::<Compression>().is_err());
+    }
+
+    #[test]
+    fn mksquashfs_opts_are_forwarded() {
+        let config = Config {
+            block_size: Some(256),
+            mksquashfs_extra: vec!["-Xdict-size 100%".to_string(), "-no-xattrs".to_string()],
+            ..Config::new("app")
+        };
+
+        let args = appimagetool_args(&config, Path::new("app"), Path::new("app.AppImage"));
+        assert_eq!(
+            args,
+            [
+                "--mksquashfs-opt", "-b", "--mksquashfs-opt", "256K", "--mksquashfs-opt", "-Xdict-size", "--mksquashfs-opt", "100%",
+                "--mksquashfs-opt", "-no-xattrs", "app", "app.AppImage",
+            ]
+        );
+        let reproducible = Config { reproducible: true, ..Config::new("app") };
+        assert_eq!(mksquashfs_opts(&reproducible), ["-all-root", "-no-xattrs"]);
+        assert!(is_block_size(4) && is_block_size(128) && is_block_size(1024));
+        assert!(!is_block_size(2) && !is_block_size(100) && !is_block_size(2048));
+    }
+
+    #[test]
+    fn update_information_is_forwarded() {
+        let info = "gh-releases-zsync|sheosi|to_appimage|latest|App-*x86_64.AppImage.zsync";
+        let config = Config {
+            update_information: Some(info.to_string()),
+            ..Config::new("app")
+        };
+
+        let args = appimagetool_args(&config, Path::new("app"), Path::new("app.AppImage"));
+        assert_eq!(args, ["-u", info, "app", "app.AppImage"]);
+
+        assert!(is_update_information(info));
+        assert!(is_update_information("zsync|https://example.com/App.AppImage.zsync"));
+        assert!(!is_update_information("gh-releases-zsync|sheosi|to_appimage"));
+        assert!(!is_update_information("ftp|example.com"));
+        assert!(!is_update_information("zsync|"));
+    }
+
+    #[test]
+    fn signing() {
+        let config = Config {
+            sign: true,
+            ..Config::new("app")
+        };
+        let args = appimagetool_args(&config, Path::new("app"), Path::new("app.AppImage"));
+        assert_eq!(args, ["--sign", "app", "app.AppImage"]);
+
+        let config = Config {
+            sign_key: Some("ABCD1234".to_string()),
+            ..Config::new("app")
+        };
+        let args = appimagetool_args(&config, Path::new("app"), Path::new("app.AppImage"));
+        assert_eq!(args, ["--sign", "--sign-key", "ABCD1234", "app", "app.AppImage"]);
+
+        assert!(check_gpg(|name| name == "gpg2").is_ok());
+        let error = check_gpg(|_| false).unwrap_err();
+        assert!(matches!(error, Error::GpgNotFound));
+        assert!(error.to_string().contains("install it"));
+    }
+
+    #[test]
+    fn launcher_script_is_preferred() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("launcher");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("install.sh"), "#!/bin/sh\n").unwrap();
+        fs::write(dir.join("run.sh"), "#!/bin/sh\n").unwrap();
+
+        let script = look_for_ext(&dir, "sh");
+
+        assert_eq!(script, Some(dir.join("run.sh")));
+    }
+
+    #[test]
+    fn tool_is_resolved() {
+        let mut conf = CliConf::default();
+        conf.tools.insert("appimagetool".to_string(), vec!["my-appimagetool".to_string()]);
+        let names = conf.tool_names("appimagetool");
+        assert_eq!(names[0], "my-appimagetool");
+
+        let installed = ["appimagetool-x86_64.AppImage", "appimagetool.appimage"];
+        assert_eq!(cmd::resolve(&names, |name| installed.contains(&name)), Some("appimagetool-x86_64.AppImage"));
+        assert_eq!(cmd::resolve(&names, |_| false), None);
+    }
+
+    #[test]
+    fn metadata_ids_agree() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("metadata");
+        fs::create_dir_all(&dir).unwrap();
+        let entry = DesktopFile::new("Calculator".to_string(), None, vec!["Utility".to_string()], false);
+        let appstream = appstream::AppStream { component: appstream::tests::calculator("com.to_appimage.calculator") };
+
+        let (desktop_file, metainfo_file) = install_metadata(&dir, &entry, &appstream).unwrap();
+        let desktop_exists = desktop_file.exists() && dir.join("com.to_appimage.calculator.desktop").exists();
+        let metainfo = fs::read_to_string(&metainfo_file).unwrap();
+
+        assert!(desktop_exists);
+        assert_eq!(desktop_file, dir.join("usr/share/applications/com.to_appimage.calculator.desktop"));
+        assert_eq!(metainfo_file, dir.join("usr/share/metainfo/com.to_appimage.calculator.appdata.xml"));
+        assert!(metainfo.contains("<launchable type=\"desktop-id\">com.to_appimage.calculator.desktop</launchable>"));
+    }
+
+    #[test]
+    fn deb_dependencies_are_bundled() {
+        let deb = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/deb/hello_1.0_all.deb");
+
+        let descriptor = serde_yaml::to_string(&deb_descriptor(&deb, None).unwrap()).unwrap();
+        for package in ["- hello", "- libgtk-3-0", "- python3", "- zlib1g"] {
+            assert!(descriptor.contains(package), "{descriptor}");
+        }
+        assert!(!descriptor.contains("libc6"));
+        assert!(!descriptor.contains("libgtk2.0-0"));
+    }
+
+    #[test]
+    fn extension_keys_are_written() {
+        let config = Config {
+            app_version: Some("1.2.3".to_string()),
+            x_keys: vec![("X-GNOME-UsesNotifications".to_string(), "true".to_string())],
+            ..Config::new("app")
+        };
+        let mut entry = DesktopFile::new("app".to_string(), None, vec!["Utility".to_string()], false);
+        entry.file.extra = extension_keys(&config, "app", config.app_version.as_deref());
+
+        let desktop = desktop_entry::to_string(&entry).unwrap();
+        assert!(desktop.contains("\nX-AppImage-Version=1.2.3\n"));
+        assert!(desktop.contains("\nX-AppImage-Name=app\n"));
+        assert!(desktop.contains("\nX-GNOME-UsesNotifications=true\n"));
+
+        assert!(is_extension_key("X-AppImage-Version"));
+        assert!(!is_extension_key("AppImage-Version"));
+        assert!(!is_extension_key("X-"));
+        assert!(!is_extension_key("X-Bad Key"));
+    }
+
+    #[test]
+    fn deb_version_everywhere() {
+        let version = deb::version(Path::new("foo_1.2.3_amd64.deb"));
+        assert_eq!(version.as_deref(), Some("1.2.3"));
+        let version = version.as_deref();
+
+        let arch = std::env::consts::ARCH;
+        assert_eq!(output_name("foo", version), Path::new(&format!("foo-1.2.3-{arch}.AppImage")));
+        assert_eq!(extension_keys(&Config::new("foo"), "foo", version)["X-AppImage-Version"], "1.2.3");
+
+        let mut component = appstream::tests::calculator("com.to_appimage.foo");
+        component.releases = appstream::Releases::current(version);
+        assert!(quick_xml::se::to_string(&component).unwrap().contains("<releases><release version=\"1.2.3\"/></releases>"));
+
+        let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/deb/hello_1.0_all.deb");
+        assert_eq!(deb::version(&fixture).as_deref(), Some("1.0"));
+    }
+
+    #[test]
+    fn github_asset_is_picked() {
+        let response = r#"{
+            "tag_name": "v1.2.3",
+            "assets": [
+                {"name": "app-1.2.3-windows-x64.zip", "browser_download_url": "https://example.com/win.zip"},
+                {"name": "app_1.2.3_amd64.deb", "browser_download_url": "https://example.com/app.deb"},
+                {"name": "app-1.2.3-x86_64.tar.gz", "browser_download_url": "https://example.com/x86_64.tar.gz"},
+                {"name": "app-1.2.3-aarch64.AppImage", "browser_download_url": "https://example.com/aarch64.AppImage"},
+                {"name": "app-1.2.3-x86_64.AppImage", "browser_download_url": "https://example.com/x86_64.AppImage"},
+                {"name": "checksums.txt", "browser_download_url": "https://example.com/checksums.txt"}
+            ]
+        }"#;
+        let mut release: github::Release = serde_json::from_str(response).unwrap();
+
+        let pick = |release: &github::Release| github::pick_asset(release, "x86_64").map(|a| a.browser_download_url.clone());
+        assert_eq!(pick(&release).as_deref(), Some("https://example.com/x86_64.AppImage"));
+
+        release.assets.retain(|a| !a.name.ends_with(".AppImage"));
+        assert_eq!(pick(&release).as_deref(), Some("https://example.com/x86_64.tar.gz"));
+
+        release.assets.retain(|a| !a.name.ends_with(".tar.gz"));
+        assert_eq!(pick(&release).as_deref(), Some("https://example.com/app.deb"));
+
+        assert_eq!(github::release_url("sheosi/to_appimage", Some("v1.0")), "https://api.github.com/repos/sheosi/to_appimage/releases/tags/v1.0");
+        assert!(matches!(github::asset_url("sheosi", None, None), Err(Error::InvalidGitHubRepo(_))));
+    }
+
+    #[test]
+    fn executable_choice_maps_back() {
+        let base = Path::new("/tmp/app");
+        let candidates = vec![base.join("app"), base.join("bin/app-cli")];
+
+        assert_eq!(pick_candidate("1", &candidates, base), Some(&candidates[0]));
+        assert_eq!(pick_candidate(" 2\n", &candidates, base), Some(&candidates[1]));
+        assert_eq!(pick_candidate("bin/app-cli", &candidates, base), Some(&candidates[1]));
+        assert_eq!(pick_candidate("0", &candidates, base), None);
+        assert_eq!(pick_candidate("3", &candidates, base), None);
+        assert_eq!(pick_candidate("ap", &candidates, base), None);
+    }
+
+    #[test]
+    fn hidden_files_are_not_executables() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("hidden");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("app"), "#!/bin/sh\n").unwrap();
+        fs::write(dir.join(".hidden"), "#!/bin/sh\n").unwrap();
+        fs::write(dir.join(".DirIcon"), "").unwrap();
+
+        let guess = guess_executable(&dir, &CliConf::default()).unwrap();
+
+        assert!(matches!(guess, ExecutableGuess::Found(exe) if exe == dir.join("app")));
+    }
+
+    #[test]
+    fn strict_needs_a_license() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("strict_license");
+        fs::create_dir_all(&dir).unwrap();
+
+        let lenient = project_license(&Config::new("app"), &dir);
+        let strict = project_license(&Config { strict: true, ..Config::new("app") }, &dir);
+
+        assert_eq!(lenient.unwrap(), licensing::License::custom("proprietary"));
+        assert!(matches!(strict, Err(Error::Strict(message)) if message.contains("no license file")));
+    }
+
+    #[test]
+    fn strict_needs_a_main_category() {
+        let strict = Config { strict: true, ..Config::new("app") };
+        let categories = vec!["IDE".to_string()];
+
+        assert!(check_categories(&Config::new("app"), &categories).is_ok());
+        assert!(matches!(check_categories(&strict, &categories), Err(Error::Strict(_))));
+        assert!(check_categories(&strict, &["Development".to_string(), "IDE".to_string()]).is_ok());
+    }
+
+    #[test]
+    fn top_level_is_classified() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("top_level");
+        let cases = ["single_dir", "single_file", "single_archive", "many"];
+        fs::create_dir_all(dir.join("single_dir/app")).unwrap();
+        fs::create_dir_all(dir.join("single_file")).unwrap();
+        fs::create_dir_all(dir.join("single_archive")).unwrap();
+        fs::write(dir.join("single_file/mytool"), "\x7fELF").unwrap();
+        fs::write(dir.join("single_archive/app.tar.gz"), "").unwrap();
+        fs::create_dir_all(dir.join("many/app")).unwrap();
+        fs::write(dir.join("many/README"), "").unwrap();
+
+        let classified = cases.map(|case| top_level(&dir.join(case)).unwrap());
+
+        assert_eq!(classified[0], TopLevel::Dir(dir.join("single_dir/app")));
+        assert_eq!(classified[1], TopLevel::File(dir.join("single_file/mytool")));
+        assert_eq!(classified[2], TopLevel::Archive(dir.join("single_archive/app.tar.gz")));
+        assert_eq!(classified[3], TopLevel::Many);
+    }
+
+    #[test]
+    fn libraries_are_bundled() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("libs");
+        let system = dir.join("system");
+        fs::create_dir_all(&system).unwrap();
+        for lib in ["libfoo.so.1", "libbar.so.2", "libc.so.6"] {
+            fs::write(system.join(lib), lib).unwrap();
+        }
+        let ldd = format!(
+            "\tlinux-vdso.so.1 (0x00007ffd)\n\tlibfoo.so.1 => {0}/libfoo.so.1 (0x00007f01)\n\tlibbar.so.2 => {0}/libbar.so.2 (0x00007f02)\n\tlibmissing.so => not found\n\tlibc.so.6 => {0}/libc.so.6 (0x00007f03)\n\t/lib64/ld-linux-x86-64.so.2 (0x00007f04)\n",
+            system.display()
+        );
+
+        let appdir = dir.join("AppDir");
+        libs::bundle(&appdir, &libs::parse_ldd(&Config::new("app"), &ldd).unwrap()).unwrap();
+        let mut bundled = fs::read_dir(appdir.join("usr/lib")).unwrap().flatten().map(|e| e.file_name()).collect::<Vec<_>>();
+        bundled.sort();
+
+        assert_eq!(bundled, ["libbar.so.2", "libfoo.so.1"]);
+        assert!(apprun(Path::new("bin/app"), true, &[]).contains("exec \"$HERE/bin/app\""));
+    }
+
+    #[test]
+    fn strict_refuses_missing_libs() {
+        let strict = Config { strict: true, ..Config::new("app") };
+        let ldd = "\tlibmissing.so => not found\n";
+
+        let res = libs::parse_ldd(&strict, ldd);
+        let lenient = libs::parse_ldd(&Config::new("app"), ldd).unwrap();
+
+        assert!(matches!(res, Err(Error::Strict(_))));
+        assert!(lenient.is_empty());
+    }
+
+    #[test]
+    fn loose_png_is_the_icon() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("loose_icon");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("app.sh"), "#!/bin/sh\n").unwrap();
+        image::RgbaImage::new(64, 64).save(dir.join("logo.png")).unwrap();
+        image::RgbaImage::new(300, 100).save(dir.join("banner.png")).unwrap();
+        image::RgbaImage::new(16, 16).save(dir.join("tiny.png")).unwrap();
+
+        let source = IconSource::detect(&dir, "app");
+
+        assert_eq!(source, IconSource::Image(dir.join("logo.png")));
+    }
+
+    #[test]
+    fn missing_wrestool_falls_back() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("no_wrestool");
+        fs::create_dir_all(&dir).unwrap();
+        let conf = CliConf { kind: CliKind::Native, ..CliConf::default() };
+        let no_wrestool = |name: &str| name != "wrestool";
+
+        let lenient = exe_icon(&conf, &Config::new("app"), &dir, &dir.join("app.exe"), no_wrestool);
+        let placeholder = dir.join("AppIcon.svg").exists();
+        let strict = exe_icon(&conf, &Config { strict: true, ..Config::new("app") }, &dir, &dir.join("app.exe"), no_wrestool);
+
+        assert!(lenient.unwrap());
+        assert!(placeholder);
+        assert!(matches!(strict, Err(Error::Strict(message)) if message.contains("install icoutils")));
+    }
+
+    #[test]
+    fn custom_default_icon() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("custom_default");
+        let appdir = dir.join("AppDir");
+        fs::create_dir_all(&appdir).unwrap();
+        let branded = dir.join("branded.png");
+        image::RgbaImage::new(64, 64).save(&branded).unwrap();
+
+        let config = Config { default_icon: Some(branded), ..Config::new("app") };
+        write_default_icon(&appdir, &config).unwrap();
+        let dimensions = image::image_dimensions(appdir.join("AppIcon.png"));
+        let builtin = appdir.join("AppIcon.svg").exists();
+
+        let broken = Config { default_icon: Some(dir.join("missing.png")), ..Config::new("app") };
+        let res = write_default_icon(&appdir, &broken);
+
+        assert_eq!(dimensions.unwrap(), (64, 64));
+        assert!(!builtin);
+        assert!(matches!(res, Err(Error::InvalidDefaultIcon { .. })));
+    }
+
+    #[test]
+    fn steps_are_timed() {
+        let mut steps = progress::Steps::new(false);
+        steps.start("download");
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        steps.start("extract");
+        steps.finish();
+        steps.finish();
+
+        let names = steps.timings().iter().map(|(name, _)| *name).collect::<Vec<_>>();
+        assert_eq!(names, ["download", "extract"]);
+        assert!(steps.timings()[0].1 >= std::time::Duration::from_millis(20));
+
+        let summary = steps.summary();
+        assert_eq!(summary.lines().count(), 3);
+        assert!(summary.lines().last().unwrap().starts_with("total"));
+    }
+
+    #[test]
+    fn data_file_is_not_an_executable() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("not_exe");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("elf"), b"\x7fELF\x02\x01").unwrap();
+        fs::write(dir.join("script"), "#!/bin/sh\nexec true\n").unwrap();
+        fs::write(dir.join("data"), "just some notes\n").unwrap();
+        let strict = Config { strict: true, ..Config::new("app") };
+
+        let elf = check_executable(&strict, &dir.join("elf"));
+        let script = check_executable(&strict, &dir.join("script"));
+        let lenient = check_executable(&Config::new("app"), &dir.join("data"));
+        let data = check_executable(&strict, &dir.join("data"));
+
+        assert!(elf.is_ok() && script.is_ok() && lenient.is_ok());
+        assert!(matches!(data, Err(Error::Strict(message)) if message.contains("neither an ELF")));
+    }
+
+    #[test]
+    fn deb_desktop_file_is_reused() {
+        let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/deb/hello_1.0_all.deb");
+
+        let desktop = deb::desktop_info(&fixture).unwrap().unwrap();
+        assert_eq!(desktop.desktop_file, "usr/share/applications/hello.desktop");
+        assert_eq!(desktop.name.as_deref(), Some("Hello World"));
+        assert_eq!(desktop.categories, ["Utility", "Education"]);
+        assert_eq!(desktop.icon.as_deref(), Some("usr/share/icons/hicolor/128x128/apps/hello.png"));
+
+        let descriptor = deb_descriptor(&fixture, Some(&desktop)).unwrap();
+        assert_eq!(descriptor.script, ["cp usr/share/applications/hello.desktop .", "cp usr/share/icons/hicolor/128x128/apps/hello.png ."]);
+    }
+
+    #[test]
+    fn deb_main_binary_names_the_app() {
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/deb");
+        let config = Config { allow_default_icon: true, ..Config::new("app") };
+
+        let hello = deb::main_binary(&fixtures.join("hello_1.0_all.deb")).unwrap().unwrap();
+        assert_eq!(hello.path, "usr/bin/hello");
+        assert_eq!(hello.icon.as_deref(), Some("usr/share/icons/hicolor/128x128/apps/hello.png"));
+
+        // Named after the package, even with other binaries next to it
+        let greeter = deb::main_binary(&fixtures.join("greeter_2.0_all.deb")).unwrap().unwrap();
+        assert_eq!(greeter, deb::MainBinary { path: "usr/bin/greeter".to_string(), icon: None });
+
+        let desktop = binary_desktop(&config, &CliConf::default(), &greeter).unwrap();
+        let contents = fs::read_to_string(&desktop.desktop_file).unwrap();
+        assert_eq!(desktop.name.as_deref(), Some("greeter"));
+        assert!(desktop.desktop_file.ends_with("greeter.desktop"));
+        assert!(desktop.icon.as_deref().is_some_and(|icon| Path::new(icon).exists()));
+        assert_eq!(desktop_entry::get_value(&contents, "Desktop Entry", "Name"), Some("greeter"));
+        assert_eq!(desktop_entry::get_value(&contents, "Desktop Entry", "Exec"), Some("greeter"));
+        assert_eq!(desktop_entry::get_value(&contents, "Desktop Entry", "Icon"), Some("AppIcon"));
+    }
+
+    #[test]
+    fn review_is_skipped_without_interaction() {
+        let config = Config { interactive: false, ..Config::new("app") };
+        let mut entry = DesktopFile::new("app".to_string(), Some("AppIcon".to_string()), vec!["Utility".to_string()], true);
+        let before = desktop_entry::to_string(&entry).unwrap();
+
+        review_entry(&config, &mut entry.file).unwrap();
+
+        assert_eq!(desktop_entry::to_string(&entry).unwrap(), before);
+        assert_eq!(entry.file.name, "app");
+        assert!(entry.file.terminal);
+    }
+
+    #[test]
+    fn tool_outside_path_is_found() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("tool_dir");
+        fs::create_dir_all(&dir).unwrap();
+        let tool = dir.join("to-appimage-test-tool");
+        fs::write(&tool, "#!/bin/sh\n").unwrap();
+        fs::write(dir.join("to-appimage-not-executable"), "").unwrap();
+        mark_executable(&tool).unwrap();
+
+        let conf = CliConf { tool_dirs: vec![dir.clone()], ..CliConf::default() };
+        let names = ["to-appimage-test-tool".to_string()];
+        let found = cmd::app_named(&names, &conf.tool_dirs()).map(|c| c.get_program().to_owned());
+        let not_executable = cmd::locate("to-appimage-not-executable", &conf.tool_dirs());
+
+        assert_eq!(found, Some(tool.into_os_string()));
+        assert_eq!(not_executable, None);
+        assert!(CliConf::default().tool_dirs().iter().any(|d| d == Path::new("/opt")));
+    }
+
+    #[test]
+    fn env_is_exported() {
+        let env = [
+            ("GDK_BACKEND".to_string(), "x11".to_string()),
+            ("GREETING".to_string(), "it's $HOME".to_string()),
+        ];
+        let script = apprun(Path::new("app"), false, &env);
+
+        assert!(script.contains("export GDK_BACKEND='x11'\n"));
+        assert!(script.contains("export GREETING='it'\\''s $HOME'\n"));
+        assert!(!script.contains("LD_LIBRARY_PATH"));
+        assert!(script.ends_with("exec \"$HERE/app\" \"$@\"\n"));
+        assert!(is_env_name("QT_QPA_PLATFORM") && is_env_name("_X1"));
+        assert!(!is_env_name("1X") && !is_env_name("A-B") && !is_env_name(""));
+    }
+
+    #[test]
+    fn bundle_extra_keeps_the_layout() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("bundle_extra");
+        let (host, appdir) = (dir.join("host/gstreamer-1.0"), dir.join("AppDir"));
+        fs::create_dir_all(host.join("codecs")).unwrap();
+        fs::create_dir_all(&appdir).unwrap();
+        fs::write(host.join("libgstcoreelements.so"), "").unwrap();
+        fs::write(host.join("codecs/libgstopus.so"), "").unwrap();
+        fs::write(host.join("README"), "").unwrap();
+
+        let pattern = format!("{}/**/*.so", host.display());
+        let copied = bundle_extra(&appdir, &pattern, "usr/lib/gstreamer-1.0").unwrap();
+        let whole = bundle_extra(&appdir, &host.to_string_lossy(), "usr/lib").unwrap();
+        let missing = bundle_extra(&appdir, "plugins/*", "usr/lib").unwrap();
+        let plugins = appdir.join("usr/lib/gstreamer-1.0");
+        let layout = [plugins.join("libgstcoreelements.so"), plugins.join("codecs/libgstopus.so"), plugins.join("README")].map(|p| p.is_file());
+
+        assert_eq!((copied, whole, missing), (2, 1, 0));
+        assert_eq!(layout, [true, true, true]);
+        assert!(check_bundle_extra("*.so", "usr/lib").is_ok());
+        assert!(matches!(check_bundle_extra("*.so", "../lib"), Err(Error::InvalidBundleExtra { .. })));
+        assert!(matches!(check_bundle_extra("*.so", "/usr/lib"), Err(Error::InvalidBundleExtra { .. })));
+    }
+
+    #[test]
+    fn host_interpreter_is_reported() {
+        let patchelf = |interpreter: &'static str, rpath: &'static str| {
+            move |flag: &str| Some(if flag == "--print-interpreter" { interpreter } else { rpath }.to_string())
+        };
+        let nix = libs::Paths::read(patchelf("/nix/store/abc-glibc-2.39/lib/ld-linux-x86-64.so.2\n", "\n"));
+        let system = libs::Paths::read(patchelf("/lib64/ld-linux-x86-64.so.2\n", "$ORIGIN/../lib\n"));
+        let rpath = libs::Paths::read(patchelf("/lib64/ld-linux-x86-64.so.2\n", "/home/dev/build/lib:$ORIGIN/lib\n"));
+        let strict = Config { strict: true, ..Config::new("app") };
+        let (appdir, exe) = (Path::new("/tmp/AppDir"), Path::new("/tmp/AppDir/bin/app"));
+
+        assert_eq!(nix.host_interpreter(), Some("/nix/store/abc-glibc-2.39/lib/ld-linux-x86-64.so.2"));
+        assert!(matches!(check_elf_paths(&strict, appdir, exe, &nix), Err(Error::Strict(message)) if message.contains("/nix/store")));
+        assert!(check_elf_paths(&strict, appdir, exe, &system).is_ok());
+        assert!(check_elf_paths(&Config::new("app"), appdir, exe, &rpath).is_ok());
+        assert!(matches!(check_elf_paths(&strict, appdir, exe, &rpath), Err(Error::Strict(message)) if message.contains("/home/dev/build/lib")));
+        assert_eq!(rpath.relative_rpath(Path::new("bin/app")), "$ORIGIN/lib:$ORIGIN/../usr/lib");
+    }
+
+    #[test]
+    fn local_descriptor_is_kept() {
+        let _staged = Staged;
+        let tmp = tempfile::tempdir().unwrap();
+        let deb = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/deb/hello_1.0_all.deb");
+        let local = tmp.path().join("hello.yaml");
+        fs::write(&local, "app: hand-tuned\n").unwrap();
+
+        let written = write_descriptor(&tmp.path().join("hello.deb"), &deb_descriptor(&deb, None).unwrap()).unwrap();
+        let kept = fs::read_to_string(&local).unwrap();
+        let generated = fs::read_to_string(&written).unwrap();
+
+        assert_eq!(kept, "app: hand-tuned\n");
+        assert_ne!(written, local);
+        assert!(generated.contains("ingredients"));
+    }
+
+    #[test]
+    fn failed_builds_are_cleaned() {
+        let tmp = tempfile::tempdir().unwrap();
+        let input = tmp.path().join("docs.zip");
+        let mut zip = zip::ZipWriter::new(fs::File::create(&input).unwrap());
+        for name in ["a.txt", "b.txt"] {
+            zip.start_file(name, zip::write::SimpleFileOptions::default()).unwrap();
+        }
+        zip.finish().unwrap();
+
+        let res = crate::build_appimage(&Config { dry_run: true, ..Config::new(input.to_str().unwrap()) });
+        // The build's own temporary root
+        let left = temp::get("").exists();
+
+        assert!(matches!(res, Err(Error::NoExecutable)), "{res:?}");
+        assert!(!left);
+    }
+
+    #[test]
+    fn patching_stages_a_copy() {
+        let _staged = Staged;
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("staged");
+        fs::create_dir_all(dir.join("bin")).unwrap();
+        fs::write(dir.join("bin/app"), "#!/bin/sh\n").unwrap();
+
+        let unchanged = staged_appdir(&Config::new("app"), dir.clone()).unwrap();
+        let patching = Config { patch_interpreter: true, ..Config::new("app") };
+        let staged = staged_appdir(&patching, dir.clone()).unwrap();
+        let restaged = staged_appdir(&patching, staged.clone()).unwrap();
+        let copied = fs::read_to_string(staged.join("bin/app")).unwrap();
+        let bundling = Config { bundle_extra: vec![("share/*".to_string(), "usr/share".to_string())], ..Config::new("app") };
+        let bundled = staged_appdir(&bundling, dir.clone()).unwrap();
+        let with_libs = staged_appdir(&Config { bundle_libs: true, ..Config::new("app") }, dir.clone()).unwrap();
+
+        assert_eq!(unchanged, dir);
+        assert!(temp::contains(&staged) && staged.ends_with(dir.file_name().unwrap()));
+        assert_eq!(restaged, staged);
+        assert_eq!(copied, "#!/bin/sh\n");
+        assert_eq!(bundled, staged);
+        assert_eq!(with_libs, staged);
+    }
+
+    #[test]
+    fn input_times_are_kept() {
+        let _staged = Staged;
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("times");
+        fs::create_dir_all(dir.join("usr/bin")).unwrap();
+        fs::write(dir.join("usr/bin/app"), "#!/bin/sh\n").unwrap();
+        let modified = |path: &Path| fs::metadata(path).unwrap().modified().unwrap();
+        let before = [modified(&dir), modified(&dir.join("usr/bin/app"))];
+
+        let staged = normalized_appdir(&dir, 1_700_000_000).unwrap();
+        let after = [modified(&dir), modified(&dir.join("usr/bin/app"))];
+        let normalized = modified(&staged.join("usr/bin/app"));
+
+        assert_eq!(before, after);
+        assert_ne!(staged, dir);
+        assert_eq!(normalized, std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000));
+    }
+
+    #[test]
+    fn stale_temp_dirs_are_removed() {
+        let tmp = tempfile::tempdir().unwrap();
+        let common = tmp.path().join("clean");
+        let (stale, current, other) = (common.join("4294967295-3"), common.join(format!("{}-0", std::process::id())), common.join("cache"));
+        for dir in [&stale, &current, &other] {
+            fs::create_dir_all(dir.join("extracted")).unwrap();
+            fs::write(dir.join("extracted").join("file"), [0u8; 10]).unwrap();
+        }
+
+        let freed = temp::remove_stale(&common, temp::is_alive).unwrap();
+        let (stale_left, current_left, other_left) = (stale.exists(), current.exists(), other.exists());
+
+        assert_eq!(freed, 10);
+        assert!(!stale_left && current_left && other_left);
+        assert!(temp::is_alive(std::process::id()));
+    }
+
+    #[test]
+    fn compressed_tarballs() {
+        assert!(archive::is_archive(Path::new("app.tar.xz")));
+        assert!(archive::is_archive(Path::new("app.tar.bz2")));
+        assert!(archive::is_archive(Path::new("app.tar.gz")));
+        assert!(!archive::is_archive(Path::new("app.tar.gz2")));
+    }
+
+    #[test]
+    fn single_extension_tarballs() {
+        for name in ["app.tgz", "app.tbz2", "app.txz"] {
+            assert!(archive::is_archive(Path::new(name)), "{name}");
+        }
+        assert!(!archive::is_archive(Path::new("app.txt")));
+
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("tgz");
+        let out = dir.join("out");
+        fs::create_dir_all(&out).unwrap();
+        let tgz = dir.join("app.tgz");
+        let encoder = flate2::write::GzEncoder::new(fs::File::create(&tgz).unwrap(), flate2::Compression::default());
+        let mut builder = tar::Builder::new(encoder);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(10);
+        header.set_mode(0o755);
+        header.set_cksum();
+        builder.append_data(&mut header, "app/run.sh", &b"#!/bin/sh\n"[..]).unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+
+        let tgz_res = archive::unarchive(&tgz, &out, &archive::Limits::default());
+        let from_tgz = fs::read_to_string(out.join("app/run.sh"));
+
+        // xz is only handled through the tar tool
+        let txz_res = (which::which("xz").is_ok() && which::which("tar").is_ok()).then(|| {
+            let tar = dir.join("app.tar");
+            let mut builder = tar::Builder::new(fs::File::create(&tar).unwrap());
+            builder.append_data(&mut header, "other/run.sh", &b"#!/bin/sh\n"[..]).unwrap();
+            builder.finish().unwrap();
+            std::process::Command::new("xz").arg(&tar).status().unwrap();
+            fs::rename(dir.join("app.tar.xz"), dir.join("app.txz")).unwrap();
+            archive::unarchive(&dir.join("app.txz"), &out, &archive::Limits::default()).map(|_| out.join("other/run.sh").is_file())
+        });
+
+        assert!(tgz_res.is_ok());
+        assert_eq!(from_tgz.unwrap(), "#!/bin/sh\n");
+        assert!(txz_res.is_none_or(|res| res.unwrap()));
+    }
+
+    #[test]
+    fn non_utf8_deb_is_an_error() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let deb = Path::new(std::ffi::OsStr::from_bytes(b"/downloads/caf\xe9_1.0_all.deb"));
+        assert!(matches!(deb_descriptor(deb, None), Err(Error::NonUtf8Path(path)) if path == deb));
+    }
+
+    #[test]
+    fn missing_input_is_reported() {
+        let missing = "/nonexistent/to_appimage/typo.tar.gz";
+
+        let res = PkgType::guess_local(missing);
+
+        assert!(matches!(&res, Err(Error::InputNotFound { path }) if path == Path::new(missing)));
+        assert_eq!(res.err().unwrap().exit_code(), 2);
+    }
+}
@@ -1,21 +1,24 @@
-use std::{path::{Path, PathBuf}, str::FromStr};
+use std::{fmt::Display, path::{Path, PathBuf}, str::FromStr};
 
-use serde::Serialize;
+use serde::{de::IntoDeserializer, Deserialize, Deserializer, Serialize, Serializer};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum Error {
-    #[error("The license couldn't be reconized")]
-    Unrecognizable,
-
     #[error("License file couldn't be found")]
     NoLicenseFile,
 
-    #[error("Couldn't read")]
+    #[error("Couldn't read the license file")]
     CouldntRead(#[from]std::io::Error),
+
+    #[error("'{0}' is not a valid SPDX license expression, use ids joined by AND, OR or WITH, like `MIT OR Apache-2.0`")]
+    InvalidExpression(String),
+
+    #[error("'{0}' can't be used for the metadata, use CC0-1.0, MIT, {ids} or a LicenseRef-", ids = SPDX_IDS.join(", "))]
+    InvalidMetadataLicense(String),
 }
 
-#[derive(Serialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum License {
     #[serde(rename = "CC0-1.0")]
     CC0, 
@@ -24,24 +27,151 @@ pub enum License {
     UniversalPermisiveLicense, 
     
     #[serde(rename = "MIT")]
-    Mit
+    Mit,
+
+    /// Several licenses combined, like `MIT OR Apache-2.0`, kept verbatim
+    #[serde(untagged, deserialize_with = "deserialize_expression")]
+    Expression(String),
+
+    /// Another of the ids AppStream accepts for metadata, like `FSFAP`
+    #[serde(untagged, deserialize_with = "deserialize_spdx")]
+    Spdx(String),
+
+    /// Any other license, written as `LicenseRef-<name>`
+    #[serde(untagged, serialize_with = "serialize_ref", deserialize_with = "deserialize_ref")]
+    Custom(String),
+}
+
+const LICENSE_REF: &str = "LicenseRef-";
+
+fn serialize_ref<S: Serializer>(name: &String, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(&format_args!("{LICENSE_REF}{name}"))
+}
+
+fn deserialize_ref<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    let id = String::deserialize(deserializer)?;
+    Ok(slug(id.strip_prefix(LICENSE_REF).unwrap_or(&id)))
+}
+
+fn deserialize_expression<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    let expression = String::deserialize(deserializer)?;
+    if is_expression(&expression) {
+        Ok(expression)
+    } else {
+        Err(serde::de::Error::custom("not a license expression"))
+    }
+}
+
+fn deserialize_spdx<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    let id = String::deserialize(deserializer)?;
+    if SPDX_IDS.contains(&id.as_str()) {
+        Ok(id)
+    } else {
+        Err(serde::de::Error::custom("not a known SPDX id"))
+    }
+}
+
+/// The metadata licenses AppStream allows besides CC0-1.0 and MIT
+const SPDX_IDS: [&str; 12] = [
+    "FSFAP",
+    "0BSD",
+    "CC-BY-3.0",
+    "CC-BY-4.0",
+    "CC-BY-SA-3.0",
+    "CC-BY-SA-4.0",
+    "GFDL-1.1",
+    "GFDL-1.2",
+    "GFDL-1.3",
+    "BSL-1.0",
+    "FTL",
+    "FSFUL",
+];
+
+const OPERATORS: [&str; 3] = ["AND", "OR", "WITH"];
+
+/// Whether `s` is a valid SPDX expression made of more than one license,
+/// only the tokens and their order are checked
+fn is_expression(s: &str) -> bool {
+    let spaced = s.replace('(', " ( ").replace(')', " ) ");
+    let tokens = spaced.split_whitespace().collect::<Vec<_>>();
+    if tokens.len() < 2 {
+        return false;
+    }
+
+    let mut expect_license = true;
+    let mut depth = 0usize;
+    for token in tokens {
+        match token {
+            "(" if expect_license => depth += 1,
+            ")" if !expect_license && depth > 0 => depth -= 1,
+            op if OPERATORS.contains(&op) && !expect_license => expect_license = true,
+            id if expect_license && is_license_id(id) => expect_license = false,
+            _ => return false,
+        }
+    }
+
+    !expect_license && depth == 0
+}
+
+fn is_license_id(id: &str) -> bool {
+    let id = id.strip_suffix('+').unwrap_or(id);
+    !id.is_empty() && !OPERATORS.contains(&id) && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-')
+}
+
+/// AppStream only allows letters, digits, `.` and `-` after `LicenseRef-`
+fn slug(name: &str) -> String {
+    let slug = name
+        .trim()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '.' { c } else { '-' })
+        .collect::<String>();
+    let slug = slug.trim_matches('-');
+
+    if slug.is_empty() {
+        "proprietary".to_string()
+    } else {
+        slug.to_string()
+    }
 }
 
 impl License {
-    pub fn locate(path: &Path) -> Result<Self, Error> {
-        fn is_license(p: &PathBuf) -> bool {
-            p.is_file() && p.file_name().unwrap_or_default().to_ascii_lowercase() == "license"
+    /// A `LicenseRef-` license, `name` is made AppStream-safe
+    pub fn custom(name: &str) -> Self {
+        License::Custom(slug(name))
+    }
+
+    /// Parses an SPDX id like `MIT` or an expression like `MIT OR Apache-2.0`,
+    /// an unknown id becomes a `LicenseRef-`
+    pub fn from_id(id: &str) -> Result<Self, Error> {
+        let id = id.trim();
+        if id.contains([' ', '(', ')']) && !is_expression(id) {
+            return Err(Error::InvalidExpression(id.to_string()));
         }
 
-        let file = std::fs::read_dir(path)
-        .unwrap()
-        .flatten()
-        .map(|d| d.path())
-        .filter(is_license)
-        .next();
+        let deserializer: serde::de::value::StrDeserializer<'_, serde::de::value::Error> = id.into_deserializer();
+        Ok(License::deserialize(deserializer).unwrap_or_else(|_| License::custom(id)))
+    }
 
-    if let Some(file) = file {
-        std::fs::read_to_string(file)?.parse().map_err(|_|Error::Unrecognizable)
+    /// A license for the AppStream metadata itself, only the permissive ones
+    /// AppStream allows or an explicit `LicenseRef-`
+    pub fn metadata(id: &str) -> Result<Self, Error> {
+        let id = id.trim();
+        match License::from_id(id) {
+            Ok(License::Custom(_)) if !id.starts_with(LICENSE_REF) => Err(Error::InvalidMetadataLicense(id.to_string())),
+            Ok(License::UniversalPermisiveLicense | License::Expression(_)) | Err(_) => Err(Error::InvalidMetadataLicense(id.to_string())),
+            license => license,
+        }
+    }
+
+    /// Finds the license file in `path` or a couple of folders below, one that
+    /// can't be recognized is named after its title
+    pub fn locate(path: &Path) -> Result<Self, Error> {
+    if let Some(file) = find_license(path, LICENSE_DEPTH)? {
+        let text = std::fs::read_to_string(file)?;
+        Ok(text.parse().unwrap_or_else(|_| {
+            let title = text.lines().map(str::trim).find(|l| !l.is_empty()).unwrap_or_default();
+            License::custom(&title.chars().take(64).collect::<String>())
+        }))
 
     } else {
         Err(Error::NoLicenseFile)
@@ -50,12 +180,158 @@ impl License {
     }
 }
 
+/// Names license files go by, compared without case
+const LICENSE_STEMS: [&str; 3] = ["license", "licence", "copying"];
+const LICENSE_EXTS: [&str; 3] = ["", "md", "txt"];
+
+/// How many folders deep licenses are looked for, like `doc/LICENSE`
+const LICENSE_DEPTH: usize = 2;
+
+fn is_license(p: &Path) -> bool {
+    let name = p.file_name().unwrap_or_default().to_string_lossy().to_lowercase();
+    let (stem, ext) = name.split_once('.').unwrap_or((name.as_str(), ""));
+    p.is_file() && LICENSE_STEMS.contains(&stem) && LICENSE_EXTS.contains(&ext)
+}
+
+// The top level goes first, then each folder in alphabetical order
+fn find_license(dir: &Path, depth: usize) -> std::io::Result<Option<PathBuf>> {
+    let mut entries = std::fs::read_dir(dir)?.flatten().map(|d| d.path()).collect::<Vec<_>>();
+    entries.sort();
+
+    if let Some(file) = entries.iter().find(|p| is_license(p)) {
+        return Ok(Some(file.clone()));
+    }
+    if depth == 0 {
+        return Ok(None);
+    }
+    Ok(entries.iter().filter(|p| p.is_dir()).find_map(|sub| find_license(sub, depth - 1).ok().flatten()))
+}
+
+/// The SPDX id, as it's serialized
+impl Display for License {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let id = serde_yaml::to_string(self).map_err(|_| std::fmt::Error)?;
+        f.write_str(id.trim_end())
+    }
+}
+
 impl FromStr for License {
     type Err = ();
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Hard-wrapped lines and CRLF endings can split the titles
+        let s = s.split_whitespace().collect::<Vec<_>>().join(" ");
         if s.contains("The Universal Permissive License (UPL), Version 1.0") {Ok(License::UniversalPermisiveLicense)}
         else if s.contains("The MIT License (Expat)") {Ok(License::Mit)}
         else {Err(())}
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::{Error, License};
+
+    #[test]
+    fn round_trip() {
+        let licenses = [
+            License::CC0,
+            License::UniversalPermisiveLicense,
+            License::Mit,
+            License::Expression("MIT OR Apache-2.0".to_string()),
+            License::Spdx("FSFAP".to_string()),
+            License::Custom("Proprietary".to_string()),
+        ];
+
+        for license in licenses {
+            let serialized = serde_yaml::to_string(&license).unwrap();
+            assert_eq!(serde_yaml::from_str::<License>(&serialized).unwrap(), license);
+        }
+    }
+
+    #[test]
+    fn read_from_config() {
+        #[derive(Deserialize)]
+        struct Config {
+            project_license: License,
+        }
+
+        let config: Config = serde_yaml::from_str("project_license: MIT").unwrap();
+        assert_eq!(config.project_license, License::Mit);
+
+        let config: Config = serde_yaml::from_str("project_license: WTFPL").unwrap();
+        assert_eq!(config.project_license, License::Custom("WTFPL".to_string()));
+        assert_eq!(serde_yaml::to_string(&config.project_license).unwrap().trim(), "LicenseRef-WTFPL");
+    }
+
+    #[test]
+    fn unrecognized_license_is_a_ref() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("license");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("LICENSE"), "\n  ACME Corp. EULA (v2)\n\nAll rights reserved.\n").unwrap();
+
+        let license = License::locate(&dir).unwrap();
+
+        let id = serde_yaml::to_string(&license).unwrap();
+        let slug = id.trim().strip_prefix("LicenseRef-").unwrap();
+        assert_eq!(slug, "ACME-Corp.-EULA--v2");
+        assert!(slug.chars().all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-'));
+    }
+
+    #[test]
+    fn license_from_id() {
+        assert_eq!(License::from_id("MIT").unwrap(), License::Mit);
+        assert_eq!(License::from_id("LicenseRef-Proprietary").unwrap(), License::Custom("Proprietary".to_string()));
+        assert_eq!(License::from_id("My_license").unwrap(), License::Custom("My-license".to_string()));
+    }
+
+    #[test]
+    fn license_display() {
+        assert_eq!(License::Mit.to_string(), "MIT");
+        assert_eq!(License::Custom("Proprietary".to_string()).to_string(), "LicenseRef-Proprietary");
+    }
+
+    #[test]
+    fn license_expressions() {
+        for expression in ["MIT OR Apache-2.0", "(MIT OR Apache-2.0) AND BSD-3-Clause", "GPL-2.0+ WITH Classpath-exception-2.0"] {
+            assert_eq!(License::from_id(expression).unwrap(), License::Expression(expression.to_string()));
+        }
+
+        for invalid in ["MIT OR", "MIT Apache-2.0", "(MIT OR Apache-2.0", "OR MIT", "MIT OR (Apache-2.0))"] {
+            assert!(matches!(License::from_id(invalid), Err(Error::InvalidExpression(_))), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn license_in_a_subfolder() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("license_deep");
+        std::fs::create_dir_all(dir.join("doc")).unwrap();
+        std::fs::write(dir.join("README"), "An app\n").unwrap();
+        std::fs::write(dir.join("doc").join("LICENSE.md"), "# The MIT License (Expat)\n").unwrap();
+
+        let license = License::locate(&dir);
+
+        assert_eq!(license.unwrap(), License::Mit);
+    }
+
+    #[test]
+    fn wrapped_crlf_license() {
+        let text = "Copyright (c) 2024 Someone\r\n\r\nThe MIT\r\n  License (Expat)\r\n\r\nPermission is hereby granted...\r\n";
+
+        assert_eq!(text.parse::<License>(), Ok(License::Mit));
+    }
+
+    #[test]
+    fn metadata_licenses() {
+        assert_eq!(License::metadata("CC0-1.0").unwrap(), License::CC0);
+        assert_eq!(License::metadata("FSFAP").unwrap(), License::Spdx("FSFAP".to_string()));
+        assert_eq!(License::metadata("LicenseRef-Docs").unwrap(), License::Custom("Docs".to_string()));
+
+        for invalid in ["GPL-3.0", "UPL-1.0", "MIT OR FSFAP"] {
+            assert!(matches!(License::metadata(invalid), Err(Error::InvalidMetadataLicense(_))), "{invalid}");
+        }
+    }
+}
@@ -1,747 +1,559 @@
-use std::{
-    fs::{self, File}, io::Write, os::unix::fs::PermissionsExt, path::{Path, PathBuf}, process::Command, str::FromStr
-};
-
-use appstream::{
-    AppStream, AppStreamComponent, ComponentType, ContentRating, Description, Launchable, LaunchableType, Provides, Screenshot, ScreenshotType, Screenshots, Url
-};
-use clap::Parser;
-use cmd::{download_to, LinkSet, RunExt};
-use image::imageops::resize;
-use itertools::Itertools;
-use licensing::License;
-use regex::Regex;
-use serde::{Deserialize, Serialize};
-use serde_yaml::to_writer;
-use thiserror::Error;
-
-
-mod appstream;
-mod desktop_entry;
-mod licensing;
-
-const DEFAULT_ICON: &[u8; 530] = include_bytes!("../default-icon.svg");
-
-const APPIMAGETOOL_LINKSET: LinkSet = LinkSet {
-    x86_64: "https://github.com/AppImage/appimagetool/releases/download/continuous/appimagetool-x86_64.AppImage",
-    arch64: "https://github.com/AppImage/appimagetool/releases/download/continuous/appimagetool-aarch64.AppImage",
-};
+use std::{io::IsTerminal, path::PathBuf, process::ExitCode};
+
+use clap::{Parser, Subcommand};
+use to_appimage::{appstream::{Control, RatingProfile}, build_appimage, build_batch, clean, inspect, inspect_dir, licensing::License, read_manifest, validate, Compression, Config};
 
 #[derive(Parser, Debug)]
-struct AppImageArgs {
-    #[arg(short, long, default_value_t = false)]
-    terminal: bool,
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
 
-    #[arg(short, long, default_value = "Utility")]
-    categories: Vec<String>,
+    #[command(flatten)]
+    build: AppImageArgs,
+}
 
-    #[arg(short, long)]
-    icon: Option<String>,
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Build an AppImage, the default when no subcommand is given
+    Build(Box<AppImageArgs>),
 
-    target: String,
-}
+    /// Print the desktop file, AppStream metadata and license of an AppImage
+    /// or AppDir
+    Inspect { appimage: PathBuf },
 
-#[derive(Serialize)]
-struct DesktopFile {
-    #[serde(rename = "Desktop Entry")]
-    file: DesktopEntry,
-}
+    /// Check the desktop file and AppStream metadata of an AppDir
+    Validate { dir: PathBuf },
+
+    /// Print what would be detected in a directory, without building
+    InspectDir {
+        dir: PathBuf,
+
+        /// Print it as JSON
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
 
-// Just here for use with skip_serializing_if
-fn is_false(val: &bool) -> bool {
-    *val
+    /// Remove the temporary folders left behind by runs that didn't finish
+    Clean,
 }
 
-#[derive(Serialize)]
-struct DesktopEntry {
-    #[serde(rename = "Name")]
-    name: String,
-    #[serde(rename = "Exec")]
-    exec: String,
-    #[serde(rename = "Icon")]
-    #[serde(skip_serializing_if = "Option::is_none")]
-    icon: Option<String>,
-    #[serde(rename = "Type")]
-    d_type: String,
-    #[serde(rename = "Categories")]
-    categories: Vec<String>,
-    #[serde(rename = "Terminal")]
-    #[serde(skip_serializing_if = "is_false")]
+#[derive(Parser, Debug)]
+struct AppImageArgs {
+    #[arg(short, long, default_value_t = false)]
     terminal: bool,
-}
 
-#[derive(Serialize)]
-struct Pkg2AppimageDescriptor {
-    app: String,
-    ingredients: Pkg2AppimageDescriptorIngredients,
+    /// Keep the app out of menus, for helpers
+    #[arg(long, default_value_t = false)]
+    no_display: bool,
 
-    #[serde(skip_serializing_if = "Vec::is_empty")]
-    script: Vec<String>,
-}
+    /// Mark the desktop entry as deleted
+    #[arg(long, default_value_t = false)]
+    hidden: bool,
 
-#[derive(Default, Serialize)]
-struct Pkg2AppimageDescriptorIngredients {
-    dist: Option<String>,
+    /// Name shown to the user, by default it's guessed from the executable
+    #[arg(short, long)]
+    name: Option<String>,
 
-    #[serde(skip_serializing_if = "Vec::is_empty")]
-    sources: Vec<String>,
+    /// Secondary label for menus, like "Web Browser"
+    #[arg(long)]
+    generic_name: Option<String>,
 
-    #[serde(skip_serializing_if = "Vec::is_empty")]
-    packages: Vec<String>,
+    /// Program the menu checks before showing the entry, looked up in PATH
+    /// unless it's an absolute path. Not written by default
+    #[arg(long)]
+    try_exec: Option<String>,
 
-    #[serde(skip_serializing_if = "Vec::is_empty")]
-    script: Vec<String>,
+    /// Executable to run, relative to the app's directory, by default it's
+    /// guessed
+    #[arg(short, long)]
+    exec: Option<String>,
 
-    #[serde(skip_serializing_if = "Vec::is_empty")]
-    debs: Vec<String>,
-}
+    /// Version of the app, for the file name and the metadata, debs are read
+    /// for it if not set
+    #[arg(long)]
+    app_version: Option<String>,
 
-impl DesktopFile {
-    pub fn new(
-        name: String,
-        icon: Option<String>,
-        categories: Vec<String>,
-        terminal: bool,
-    ) -> Self {
-        Self {
-            file: DesktopEntry {
-                name,
-                exec: "./AppRun".to_string(),
-                d_type: "Application".to_string(),
-                icon,
-                categories,
-                terminal,
-            },
-        }
-    }
-}
+    /// Extra desktop file key, as `X-Key=value`, can be repeated
+    #[arg(long = "x-key", value_parser = parse_key_value)]
+    x_keys: Vec<(String, String)>,
 
-#[derive(Copy, Clone, Default, Deserialize)]
-enum CliKind {
-    Native,
-    #[default]
-    Toolbox,
-}
+    /// Categories for the desktop file, can be repeated or comma-separated,
+    /// `Utility` by default
+    #[arg(short, long, value_delimiter = ',', value_parser = parse_category)]
+    categories: Vec<String>,
 
-#[derive(Deserialize)]
-struct CliConf {
-    #[serde(default)]
-    kind: CliKind,
+    /// Search term for menus and software centers, can be repeated
+    #[arg(short, long = "keyword")]
+    keywords: Vec<String>,
 
-    #[serde(default = "default_container_name")]
-    container_name: String,
-}
+    /// Set of categories to add, one of game, dev, multimedia, graphics,
+    /// office or network
+    #[arg(long)]
+    preset: Option<String>,
 
-fn default_container_name() -> String {
-    "ubuntu-toolbox-22.04".to_string()
-}
-
-impl Default for CliConf {
-    fn default() -> CliConf {
-        CliConf {
-            kind: CliKind::Toolbox,
-            container_name: default_container_name(),
-        }
-    }
-}
+    #[arg(short, long)]
+    icon: Option<String>,
 
-fn extract_icon_from_exe(conf: &CliConf, dir: &Path, file: &str) {
-    cmd::app_from("wrestool", conf.kind, Some(&conf.container_name))
-        .unwrap()
-        .arg("-x")
-        .arg("--output=icon.ico")
-        .arg("-t")
-        .arg("14")
-        .arg(file)
-        .run()
-        .unwrap();
-
-    cmd::app_from("icotool", CliKind::Native, Some(&conf.container_name))
-        .unwrap()
-        .arg("-x")
-        .arg("icon.ico")
-        .arg("-h")
-        .arg("256")
-        .arg("-o")
-        .arg(dir.join("AppIcon.png"))
-        .run_outerr()
-        .unwrap();
-
-    std::fs::remove_file("icon.ico").unwrap();
-}
+    /// Download the icon from this URL, a local --icon takes precedence
+    #[arg(long)]
+    icon_url: Option<String>,
+
+    /// Let --strict builds use the placeholder icon when none is found
+    #[arg(long, default_value_t = false)]
+    allow_default_icon: bool,
+
+    /// Image to use as the placeholder icon instead of the built-in one
+    #[arg(long, env = "TO_APPIMAGE_DEFAULT_ICON", value_name = "PATH")]
+    default_icon: Option<PathBuf>,
+
+    /// Bundle the shared libraries the executable needs, except glibc's
+    #[arg(long, default_value_t = false)]
+    bundle_libs: bool,
+
+    /// Copy what a glob matches into a directory of the AppDir, as
+    /// `glob:dir`, like `/usr/lib/x86_64-linux-gnu/gstreamer-1.0/*:usr/lib/gstreamer-1.0`.
+    /// Relative globs are looked up in the app, can be repeated
+    #[arg(long, value_parser = parse_bundle_extra, value_name = "GLOB:DIR")]
+    bundle_extra: Vec<(String, String)>,
+
+    /// Point an interpreter and RPATH that only exist on this system to the
+    /// system loader and the AppDir's libraries, needs patchelf
+    #[arg(long, default_value_t = false)]
+    patch_interpreter: bool,
+
+    /// Environment variable the AppRun sets, as `KEY=value`, can be repeated
+    #[arg(long = "env", value_parser = parse_key_value)]
+    env: Vec<(String, String)>,
+
+    /// One-line description of the app
+    #[arg(long)]
+    summary: Option<String>,
+
+    /// Long description, blank lines separate paragraphs and lines starting
+    /// with `- ` make a list
+    #[arg(long)]
+    description: Option<String>,
+
+    /// Read the long description from this file, it takes precedence over
+    /// --description
+    #[arg(long)]
+    description_file: Option<PathBuf>,
+
+    /// Language the name and summary are written in, like `es` or `pt-BR`
+    #[arg(long)]
+    default_lang: Option<String>,
+
+    /// Translated name for the AppStream metadata, as `lang:text`
+    #[arg(long, value_parser = parse_locale_pair)]
+    name_locale: Vec<(String, String)>,
+
+    /// Translated summary for the AppStream metadata, as `lang:text`
+    #[arg(long, value_parser = parse_locale_pair)]
+    summary_locale: Vec<(String, String)>,
+
+    /// Reverse-DNS id for the app, by default one is made from the executable
+    #[arg(long)]
+    app_id: Option<String>,
+
+    /// Umbrella project for the AppStream metadata, like GNOME or KDE
+    #[arg(long)]
+    project_group: Option<String>,
+
+    /// Maintainer's email for the AppStream metadata
+    #[arg(long)]
+    update_contact: Option<String>,
+
+    /// SPDX id or expression for the app's license, like `MIT`,
+    /// `MIT OR Apache-2.0` or `LicenseRef-Proprietary`, by default it's
+    /// detected from the LICENSE file
+    #[arg(long, value_parser = parse_license)]
+    license: Option<License>,
+
+    /// License of the AppStream metadata: CC0-1.0, MIT, FSFAP and the other
+    /// ids AppStream allows, or a `LicenseRef-`
+    #[arg(long, value_parser = parse_metadata_license, default_value = "CC0-1.0")]
+    metadata_license: License,
+
+    /// Input method the app works well with: pointing, keyboard, touch or
+    /// gamepad, can be repeated
+    #[arg(long)]
+    supports: Vec<Control>,
+
+    /// OARS content rating: none, mild or teen
+    #[arg(long)]
+    rating_profile: Option<RatingProfile>,
+
+    /// Don't validate the generated AppStream metadata
+    #[arg(long, default_value_t = false)]
+    no_appstream_validate: bool,
+
+    /// Always download remote targets instead of reusing a cached copy
+    #[arg(long, default_value_t = false)]
+    no_cache: bool,
+
+    /// Fail instead of downloading anything, cached copies are still used
+    #[arg(long, default_value_t = false)]
+    offline: bool,
+
+    /// Expected SHA-256 of a remote target
+    #[arg(long)]
+    checksum: Option<String>,
+
+    /// Release to build for gh:owner/repo targets, the latest one by default
+    #[arg(long)]
+    tag: Option<String>,
+
+    /// Give up on downloads taking longer than this many seconds
+    #[arg(long)]
+    download_timeout: Option<u64>,
+
+    /// Stop external commands running for longer than this many seconds
+    #[arg(long, default_value_t = 300)]
+    timeout: u64,
+
+    /// Refuse archives extracting to more than this many MiB
+    #[arg(long, default_value_t = 4096)]
+    max_extract_size: u64,
+
+    /// Compressor for the AppImage: gzip, xz or zstd, appimagetool picks one
+    /// if not set
+    #[arg(long)]
+    compression: Option<Compression>,
+
+    /// Squashfs block size in KiB, a power of two from 4 to 1024
+    #[arg(long, value_name = "KIB")]
+    block_size: Option<u32>,
+
+    /// Extra arguments for mksquashfs, like `--mksquashfs-extra='-Xdict-size
+    /// 100%'`, can be repeated
+    #[arg(long, allow_hyphen_values = true, value_name = "ARGS")]
+    mksquashfs_extra: Vec<String>,
+
+    /// Update information for AppImageUpdate, like
+    /// `gh-releases-zsync|user|repo|latest|App-*x86_64.AppImage.zsync`
+    #[arg(short, long)]
+    update_information: Option<String>,
 
-fn look_for_ext(path: &PathBuf, ext: &str) -> Option<PathBuf> {
-    std::fs::read_dir(path)
-        .unwrap()
-        .flatten()
-        .map(|d| d.path())
-        .find(|p| {
-            p.is_file()
-                && p.extension()
-                    .map(|e| e.to_str().unwrap_or(""))
-                    .unwrap_or("")
-                    == ext
-        })
-}
+    /// Sign the AppImage with gpg's default key
+    #[arg(long, default_value_t = false)]
+    sign: bool,
 
-fn look_for_no_exts(path: &PathBuf) -> Vec<PathBuf> {
-    #[allow(clippy::ptr_arg)]
-    fn is_exe_no_ext(p: &PathBuf) -> bool {
-        let file_name_lower = p
-            .file_name()
-            .unwrap()
-            .to_string_lossy()
-            .into_owned()
-            .to_lowercase();
-        p.is_file()
-            && p.extension().is_none()
-            && !["legal_details", "license", "readme", "apprun", ".diricon"].contains(&file_name_lower.as_str())
-    }
-    std::fs::read_dir(path)
-        .unwrap()
-        .flatten()
-        .map(|d| d.path())
-        .filter(is_exe_no_ext)
-        .collect()
-}
+    /// Sign the AppImage with this gpg key
+    #[arg(long)]
+    sign_key: Option<String>,
 
-#[derive(Debug, Error)]
-enum Error {
-    #[error("unsupported archive format '{0}'")]
-    ArchiveFormatNotSupported(String),
-}
+    /// Prepare everything but don't produce the AppImage
+    #[arg(long, default_value_t = false)]
+    dry_run: bool,
 
-mod archive {
-    use crate::{cmd, cmd::RunExt, Error};
-    use itertools::Itertools;
-    use path_utils::PathExt;
-    use std::path::Path;
-
-    pub fn is_archive(path: &Path) -> bool {
-        // Due to how this works, the extensions are reversed, that's why they
-        // are written this way
-        ["zip", "tar", "gz.tar", "gz2.tar", "7z"]
-            .contains(&path.extensions_lossy().join(".").as_str())
-    }
+    /// Write a JSON report of the build, with the error if it failed, `-`
+    /// prints it instead
+    #[arg(long, value_name = "PATH")]
+    json_report: Option<PathBuf>,
 
-    enum Archive {
-        Zip,
-        Tar, // Everything can be processed by the tar tool, so we are making no distinctions
-    }
+    /// Never ask anything, even when run from a terminal
+    #[arg(long, default_value_t = false)]
+    non_interactive: bool,
 
-    impl Archive {
-        fn guess<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
-            match path.as_ref().extensions_lossy().join(".").as_str() {
-                "zip" => Ok(Archive::Zip),
-                "gz.tar" | "tar" => Ok(Archive::Tar),
-                a => Err(Error::ArchiveFormatNotSupported(a.to_string())),
-            }
-        }
-    }
+    /// Log each phase of the build with how long it took, and a summary at
+    /// the end
+    #[arg(long, default_value_t = false)]
+    timings: bool,
 
-    pub fn unarchive<P2>(input: &Path, output: P2) -> Result<(), Error>
-    where
-        P2: AsRef<Path>,
-    {
-        match Archive::guess(input)? {
-            Archive::Zip => {
-                cmd::app("unzip")
-                    .unwrap()
-                    .arg(input)
-                    .arg("-d")
-                    .arg(output.as_ref())
-                    .run()
-                    .unwrap();
-                Ok(())
-            }
-            Archive::Tar => {
-                cmd::app("tar")
-                    .unwrap()
-                    .arg("-xf")
-                    .arg(input)
-                    .arg("-C")
-                    .arg(output.as_ref())
-                    .run()
-                    .unwrap();
-                Ok(())
-            }
-        }
-    }
-}
+    /// Print what appimagetool and the other tools output even when they
+    /// succeed, they always do when they fail
+    #[arg(short, long, default_value_t = false)]
+    verbose: bool,
 
-fn resize_img(input: &Path, output: &Path) -> image::ImageResult<()> {
-    use image::ImageReader;
+    /// Produce the same AppImage byte by byte every time, file times come
+    /// from SOURCE_DATE_EPOCH or are 0
+    #[arg(long, default_value_t = false)]
+    reproducible: bool,
 
-    let img = ImageReader::open(input)?.decode()?;
-    resize(&img, 256, 256, image::imageops::FilterType::Lanczos3).save(output)
-}
+    /// Only write the desktop file, the AppStream metadata and the icon to
+    /// this directory, no AppImage is produced
+    #[arg(long, value_name = "DIR")]
+    export_metadata: Option<PathBuf>,
 
-trait ExtUtils {
-    fn get_ext(&self) -> &str;
-    fn is_ext(&self, ext: &str) -> bool;
-}
+    /// Copy the staged AppDir, with the app, its AppRun and all the metadata,
+    /// to this directory instead of producing an AppImage
+    #[arg(long, value_name = "DIR", conflicts_with = "export_metadata")]
+    appdir_out: Option<PathBuf>,
 
-impl ExtUtils for &Path {
-    fn get_ext(&self) -> &str {
-        self.extension().unwrap_or_default().to_str().unwrap_or("")
-    }
-    fn is_ext(&self, ext: &str) -> bool {
-        self.get_ext() == ext
-    }
+    /// Overwrite the output AppImage if it already exists
+    #[arg(short, long, default_value_t = false)]
+    force: bool,
+
+    /// Also write the AppImage's SHA-256 to `<output>.sha256`
+    #[arg(long, default_value_t = false)]
+    write_checksum: bool,
+
+    /// Fail instead of warning, like on the placeholder icon, missing
+    /// icoutils, a missing or unrecognized license, no main category, skipped
+    /// AppStream validation or a library that can't be bundled
+    #[arg(long, default_value_t = false)]
+    strict: bool,
+
+    /// Toolbox container where Windows tools are run
+    #[arg(long)]
+    container: Option<String>,
+
+    /// File with one target per line (or a YAML list of entries), `-` reads
+    /// them from stdin
+    #[arg(short, long, conflicts_with = "target")]
+    manifest: Option<PathBuf>,
+
+    /// Path or URL of the app, or gh:owner/repo for its latest GitHub release
+    #[arg(required_unless_present = "manifest")]
+    target: Option<String>,
 }
 
-impl ExtUtils for PathBuf {
-    fn get_ext(&self) -> &str {
-        self.extension().unwrap_or_default().to_str().unwrap_or("")
-    }
-    fn is_ext(&self, ext: &str) -> bool {
-        self.get_ext() == ext
+fn parse_category(s: &str) -> Result<String, String> {
+    let category = s.trim();
+    if category.is_empty() {
+        Err("categories can't be empty".to_string())
+    } else {
+        Ok(category.to_string())
     }
 }
 
-
-fn download_to_temp(tmp_path: &Path, url: &str) -> String {
-    (&mut download_to(url, tmp_path)).run().unwrap();
-
-    tmp_path
-        .read_dir()
-        .unwrap()
-        .next()
-        .unwrap()
-        .unwrap()
-        .path()
-        .to_str()
-        .unwrap()
-        .to_owned()
+fn parse_license(s: &str) -> Result<License, String> {
+    License::from_id(s).map_err(|e| e.to_string())
 }
 
-enum PkgType {
-    Deb(PathBuf),
-    Yaml(PathBuf),
-    Other(PathBuf),
+fn parse_metadata_license(s: &str) -> Result<License, String> {
+    License::metadata(s).map_err(|e| e.to_string())
 }
 
-mod temp {
-    use std::{
-        fs,
-        path::{Path, PathBuf},
-    };
-    fn get_common() -> PathBuf {
-        Path::new("/tmp/to_appimage").into()
-    }
-    fn get_base() -> PathBuf {
-        get_common().join(std::process::id().to_string())
+fn parse_key_value(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("'{s}' is not in the form KEY=value")),
     }
+}
 
-    pub fn get(identifier: &str) -> PathBuf {
-        get_base().join(identifier)
+/// Without a `:dir` the matches go to the AppDir's root
+fn parse_bundle_extra(s: &str) -> Result<(String, String), String> {
+    match s.rsplit_once(':') {
+        Some((glob, dir)) if !glob.is_empty() => Ok((glob.to_string(), dir.to_string())),
+        Some(_) => Err(format!("'{s}' is not in the form glob:dir")),
+        None => Ok((s.to_string(), String::new())),
     }
+}
 
-    pub fn try_create(identifier: &str) -> PathBuf {
-        let tmp_path = get(identifier);
-        if !tmp_path.exists() {
-            fs::create_dir_all(&tmp_path).unwrap();
-        }
-        tmp_path
-    }
-
-    pub fn clean_everything() {
-        let tmp_path = get_base();
-        if tmp_path.exists() {
-            fs::remove_dir_all(&tmp_path).unwrap();
-        }
-
-        // Erase /tmp/to_appimage if it's empty
-        let common = get_common();
-        if common.exists() && common.read_dir().unwrap().next().is_none() {
-            std::fs::remove_dir(common).unwrap();
-        }
+fn parse_locale_pair(s: &str) -> Result<(String, String), String> {
+    match s.split_once(':') {
+        Some((lang, text)) if !lang.is_empty() => Ok((lang.to_string(), text.to_string())),
+        _ => Err(format!("'{s}' is not in the form lang:text")),
     }
 }
 
-impl PkgType {
-    fn guess(input: &str) -> Self {
-        if input.starts_with("http") {
-            let temp = temp::try_create("download");
-            let temp_data = download_to_temp(&temp, input);
-            Self::guess_local(&temp_data)
-        } else {
-            Self::guess_local(input)
+impl From<AppImageArgs> for Config {
+    fn from(args: AppImageArgs) -> Self {
+        Config {
+            terminal: args.terminal,
+            no_display: args.no_display,
+            hidden: args.hidden,
+            name: args.name,
+            generic_name: args.generic_name,
+            try_exec: args.try_exec,
+            exec: args.exec,
+            app_version: args.app_version,
+            x_keys: args.x_keys,
+            categories: args.categories,
+            preset: args.preset,
+            keywords: args.keywords,
+            icon: args.icon,
+            icon_url: args.icon_url,
+            allow_default_icon: args.allow_default_icon,
+            default_icon: args.default_icon,
+            bundle_libs: args.bundle_libs,
+            bundle_extra: args.bundle_extra,
+            env: args.env,
+            patch_interpreter: args.patch_interpreter,
+            summary: args.summary,
+            description: args.description,
+            description_file: args.description_file,
+            name_locale: args.name_locale,
+            summary_locale: args.summary_locale,
+            default_lang: args.default_lang,
+            app_id: args.app_id,
+            project_group: args.project_group,
+            update_contact: args.update_contact,
+            license: args.license,
+            metadata_license: args.metadata_license,
+            supports: args.supports,
+            rating_profile: args.rating_profile,
+            no_appstream_validate: args.no_appstream_validate,
+            no_cache: args.no_cache,
+            offline: args.offline,
+            checksum: args.checksum,
+            tag: args.tag,
+            download_timeout: args.download_timeout.map(std::time::Duration::from_secs),
+            timeout: std::time::Duration::from_secs(args.timeout),
+            max_extract_size: args.max_extract_size.saturating_mul(1024 * 1024),
+            compression: args.compression,
+            block_size: args.block_size,
+            mksquashfs_extra: args.mksquashfs_extra,
+            update_information: args.update_information,
+            sign: args.sign,
+            sign_key: args.sign_key,
+            dry_run: args.dry_run,
+            export_metadata: args.export_metadata,
+            appdir_out: args.appdir_out,
+            json_report: args.json_report,
+            timings: args.timings,
+            verbose: args.verbose,
+            reproducible: args.reproducible,
+            force: args.force,
+            write_checksum: args.write_checksum,
+            strict: args.strict,
+            container: args.container,
+            interactive: !args.non_interactive && std::io::stdin().is_terminal(),
+            target: args.target.unwrap_or_default(),
         }
     }
+}
 
-    fn guess_local(input: &str) -> Self {
-        let path = PathBuf::from_str(input).unwrap().canonicalize().unwrap();
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let res = match cli.command {
+        None => return run_build(cli.build),
+        Some(Command::Build(args)) => return run_build(*args),
+        Some(Command::Inspect { appimage }) => inspect(&appimage).map(|inspection| {
+            let none = "(none)".to_string();
+            println!("Desktop file:\n{}\n", inspection.desktop_entry.unwrap_or_else(|| none.clone()).trim_end());
+            println!("AppStream metadata:\n{}\n", inspection.metainfo.unwrap_or_else(|| none.clone()).trim_end());
+            println!("License: {}", inspection.license.map(|l| l.to_string()).unwrap_or(none));
+        }),
+        Some(Command::Validate { dir }) => validate(&dir).map(|_| println!("{} is valid", dir.display())),
+        Some(Command::InspectDir { dir, json }) => inspect_dir(&dir).map(|detection| {
+            if json {
+                println!("{}", serde_json::to_string_pretty(&detection).unwrap_or_default());
+            } else {
+                println!("Executable: {}", detection.executable.display());
+                if !detection.candidates.is_empty() {
+                    println!("Candidates: {}", detection.candidates.iter().map(|c| c.display().to_string()).collect::<Vec<_>>().join(", "));
+                }
+                println!("Icon: {:?}", detection.icon);
+                println!("License: {}", detection.license.map(|l| l.to_string()).unwrap_or_else(|| "(none)".to_string()));
+                println!("Categories: {}", detection.categories.join(";"));
+            }
+        }),
+        Some(Command::Clean) => clean().map(|freed| println!("Freed {} KiB", freed.div_ceil(1024))),
+    };
 
-        if path.is_ext("deb") {
-            PkgType::Deb(path)
-        } else if path.is_ext("yaml") {
-            PkgType::Yaml(path)
-        } else {
-            PkgType::Other(path)
+    match res {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            ExitCode::from(e.exit_code())
         }
     }
 }
 
-fn run_pkgtoappimage(yml: &Path) {
-    let status = Command::new("gearlever_pkg2appimage_02a375.appimage")
-        .arg(yml)
-        .output()
-        .unwrap();
+fn run_build(mut args: AppImageArgs) -> ExitCode {
+    let manifest = args.manifest.take();
+    let config = Config::from(args);
 
-    if !status.status.success() {
-        dialog::Message::new(String::from_utf8(status.stderr).unwrap());
+    if let Some(manifest) = manifest {
+        return run_batch(&config, &manifest);
     }
-}
 
-fn mark_executable(path: &Path) {
-    let mut perms = std::fs::metadata(path).expect("Failed to obtain metadata").permissions();
-    let mode = perms.mode();
-    let user_perms = ((mode/64) | 1) * 64;
-    let group_perms = (((mode % 64)/8) | 1) * 8;
-    let others_perms = (mode % 8) | 1 ;
-    perms.set_mode(user_perms + group_perms + others_perms);
-    fs::set_permissions(path, perms).expect("Failed to set metatadata");
+    match build_appimage(&config) {
+        // The report already went to stdout
+        Ok(_) if config.json_report.as_deref() == Some(std::path::Path::new("-")) => ExitCode::SUCCESS,
+        Ok(output) if config.export_metadata.is_some() => {
+            println!("Metadata written to {}", output.display());
+            ExitCode::SUCCESS
+        }
+        Ok(appdir) if config.appdir_out.is_some() => {
+            println!("AppDir written to {}", appdir.display());
+            ExitCode::SUCCESS
+        }
+        Ok(appimage) => {
+            println!("AppImage written to {}", appimage.display());
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("Error: {e}");
+            ExitCode::from(e.exit_code())
+        }
+    }
 }
 
-mod cmd {
-    use std::{path::Path, process::Command};
-
-    use crate::{mark_executable, CliKind, Error};
+fn run_batch(config: &Config, manifest: &std::path::Path) -> ExitCode {
+    let entries = match read_manifest(manifest) {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            return ExitCode::from(e.exit_code());
+        }
+    };
 
-    pub fn app(name: &str) -> Option<Command> {
-        which::which(name).ok().map(Command::new)
-    }
+    let results = build_batch(config, entries);
+    let failures = results.iter().filter(|(_, res)| res.is_err()).count();
 
-    pub fn app_from(name: &str, kind: CliKind, container: Option<&str>) -> Option<Command> {
-        if matches!(kind, CliKind::Native) {
-            app(name)
-        } else {
-            Some(app_from_toolbox(container.unwrap(), name))
+    println!("\nSummary:");
+    for (target, res) in &results {
+        match res {
+            Ok(appimage) => println!("  ok    {target} -> {}", appimage.display()),
+            Err(e) => println!("  error {target}: {e}"),
         }
     }
+    println!("{} succeeded, {failures} failed", results.len() - failures);
 
-    fn app_from_toolbox(container: &str, command: &str) -> Command {
-        let mut c = Command::new("/usr/bin/toolbox");
-        c.arg("run").arg("-c").arg(container).arg(command);
-        c
-    }
-    
-    pub fn download_as(url: &str, file_name: &Path) {
-        let out_path_str = file_name.to_str().expect("Out must be UTF-8");
-        if let Some(mut curl) = app("curl") {
-            curl.args(["-L", url, "-o", out_path_str]).run().unwrap();
-        } else if let Some(mut wget) = app("wget") {
-            wget.args([url, "-O", out_path_str]).run().unwrap();
-        } else {
-            panic!("There's no available program for downloading files!")
-        }
-    }
-    
-
-    pub fn download_to(url: &str, dir_path: &Path) -> Command {
-        let dir_path_str = dir_path.to_str().expect("Path must be UTF-8 valid");
-        if let Some(mut curl) = app("curl") {
-            curl.args(["-O", "-L", "--output-dir", dir_path_str, url]);
-            curl
-        } else if let Some(mut wget) = app("wget") {
-            wget.args([url, "-P", dir_path_str]);
-            wget
-        } else {
-            panic!("There's no available program for downloading files!")
-        }
+    if failures == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
     }
+}
 
- 
-    
-    const CACHE_DIR: &str = ".local/Apps";
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
 
-    pub struct  LinkSet {
-        pub x86_64: &'static str,
-        pub arch64: &'static str
+    use crate::{AppImageArgs, Cli, Command};
+
+    #[test]
+    fn categories_repeated() {
+        let args = AppImageArgs::try_parse_from(["to_appimage", "--categories", "Utility", "--categories", "Development", "app"]).unwrap();
+        assert_eq!(args.categories, vec!["Utility", "Development"]);
     }
 
-    impl LinkSet {
-        pub fn get_current(&self) -> &str {
-            match std::env::consts::ARCH {
-                "aarch64"=> &self.arch64,
-                "x86_64" => &self.x86_64,
-                _ => panic!("Architecture not supported!")
-            }
-        }
+    #[test]
+    fn categories_comma_separated() {
+        let args = AppImageArgs::try_parse_from(["to_appimage", "--categories", "Utility, Development", "app"]).unwrap();
+        assert_eq!(args.categories, vec!["Utility", "Development"]);
     }
 
-    pub fn cached_app(name: &str, link_set: &LinkSet) -> Command {
-        app(name).unwrap_or_else(||{
-            let cache_dir = directories::UserDirs::new().expect("Can't locate user's directories").home_dir().join(CACHE_DIR);
-            if !cache_dir.exists() {
-                std::fs::create_dir_all(&cache_dir).expect("Couldn't create cached dir");
-            }
+    #[test]
+    fn build_is_the_default() {
+        let cli = Cli::try_parse_from(["to_appimage", "--terminal", "app"]).unwrap();
+        assert!(cli.command.is_none());
+        assert_eq!(cli.build.target.as_deref(), Some("app"));
 
-            let cached_app = cache_dir.join(name);
-            app(cached_app.to_str().expect("path must be UTF-8 valid")).unwrap_or_else(||{
-                download_as(link_set.get_current(), &cached_app);
-                mark_executable(&cached_app);
-                Command::new(cached_app)
-            })
-        })
-        
+        let cli = Cli::try_parse_from(["to_appimage", "build", "--terminal", "app"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Build(args)) if args.terminal && args.target.as_deref() == Some("app")));
     }
 
-    pub trait RunExt {
-        fn run(&mut self) -> Result<(), Error>;
-        fn run_outerr(&mut self) -> Result<(), Error>;
+    #[test]
+    fn inspect_subcommand() {
+        let cli = Cli::try_parse_from(["to_appimage", "inspect", "App.AppImage"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Inspect { appimage }) if appimage.to_str() == Some("App.AppImage")));
     }
 
-    impl RunExt for &mut Command {
-        // TODO: Actually produce errors from this
-        fn run(&mut self) -> Result<(), Error> {
-            let out = self.status().unwrap();
-            assert!(out.success());
-            Ok(())
-        }
-
-        fn run_outerr(&mut self) -> Result<(), Error> {
-            let out = self.output().unwrap();
-            if !out.status.success() {
-                println!("{}", String::from_utf8(out.stderr).unwrap());
-            }
-
-            assert!(out.status.success());
-            Ok(())
-        }
+    #[test]
+    fn bundle_extra_spec() {
+        let args = AppImageArgs::try_parse_from(["to_appimage", "--bundle-extra", "/usr/lib/gst/*:usr/lib/gst", "--bundle-extra", "share/*", "app"]).unwrap();
+        assert_eq!(args.bundle_extra, [("/usr/lib/gst/*".to_string(), "usr/lib/gst".to_string()), ("share/*".to_string(), String::new())]);
     }
-}
 
-fn main() {
-    use dialog::DialogBox;
-
-    let conf = CliConf::default();
-    let args = AppImageArgs::parse();
-
-    match PkgType::guess(&args.target) {
-        PkgType::Deb(input) => {
-            let name_reg = Regex::new("^[A-Za-z-0-9]*").unwrap();
-            let name = name_reg
-                .captures(input.file_name().unwrap().to_str().unwrap())
-                .unwrap()
-                .get(0)
-                .unwrap()
-                .as_str();
-
-            let descriptor = Pkg2AppimageDescriptor {
-                app: name.to_string(),
-                ingredients: Pkg2AppimageDescriptorIngredients {
-                    dist: Some("trusty".to_string()),
-                    packages: vec![name.replace(' ', "-").to_lowercase()],
-                    sources: vec![
-                        "deb http://archive.ubuntu.com/ubuntu/ trusty main universe".to_string()
-                    ],
-                    debs: vec![input.to_str().unwrap().to_string()],
-                    ..Default::default()
-                },
-                script: vec!["ls".to_string()],
-            };
-
-            let with_yaml_ext = input.with_extension("yaml");
-            let p_descriptor = with_yaml_ext.file_name().unwrap();
-            let f_descriptor = File::create(p_descriptor).unwrap();
-            to_writer(&f_descriptor, &descriptor).unwrap();
-            run_pkgtoappimage(Path::new(p_descriptor));
-        }
-        PkgType::Yaml(input) => {
-            run_pkgtoappimage(&input);
-        }
-        PkgType::Other(input) => {
-            let actual_input = if archive::is_archive(&input) {
-                let tmp_path = temp::try_create(
-                    input
-                        .file_stem()
-                        .map(|s| s.to_str().unwrap_or(""))
-                        .unwrap_or("archive_out"),
-                );
-
-                // Clean any leftover temporary files, this makes using unarchiver
-                // way easier
-                if tmp_path.exists() {
-                    std::fs::remove_dir_all(&tmp_path).unwrap();
-                }
-                fs::create_dir_all(&tmp_path).unwrap();
-
-                archive::unarchive(&input, &tmp_path).unwrap();
-
-                if fs::read_dir(&tmp_path).unwrap().count() == 1 {
-                    // Count consumes the whole iterator and ReadDir can't be cloned,
-                    // so we need to read the directory
-                    if let Some(Ok(first_item)) = fs::read_dir(&tmp_path).unwrap().next() {
-                        first_item.path()
-                    } else {
-                        tmp_path
-                    }
-                } else {
-                    tmp_path
-                }
-            } else {
-                input
-            };
-
-            fn valid_icon(path: &Option<String>) -> Option<PathBuf> {
-                if let Some(icon) = path {
-                    let path = Path::new(icon).to_path_buf();
-                    if path.exists() {
-                        Some(path)
-                    }
-                    else { None}
-                }
-                else { None}
-            }
-
-            // Due to how the pkg2appimagetool works we NEED an icon, that's why it isn't an
-            // option
-            let icon = 
-            if let Some(icon) = valid_icon(&args.icon) {
-                fs::copy(icon, actual_input.join("AppIcon.png")).expect("Couldn't write AppIcon");
-                "AppIcon".to_string()
-            }
-            else if actual_input.join("AppIcon.png").exists() || actual_input.join("AppIcon.svg").exists() {
-                "AppIcon".to_string()
-            } else if let Some(exe_name) = look_for_ext(&actual_input, "exe") {
-                extract_icon_from_exe(&conf, &actual_input, exe_name.to_str().unwrap());
-                "AppIcon".to_string()
-            } else {
-                    dialog::Message::new("No icon found, writing one")
-                        .show()
-                        .expect("Couldn't show message");
-                    File::create(actual_input.join("AppIcon.svg")).expect("This should be possible").write(DEFAULT_ICON).expect("Failed to write icon");
-                    "AppIcon".to_string()
-            };
-
-            let executable = if let Some(shell_file) = look_for_ext(&actual_input, "sh") {
-                shell_file
-            } else if let Some(linux_exe) = look_for_ext(&actual_input, "x86_64") {
-                linux_exe
-            } else {
-                let mut exes = look_for_no_exts(&actual_input);
-                if exes.is_empty() {
-                    panic!("Couldn't find any suitable executable")
-                } else if exes.len() == 1 {
-                    exes.first().unwrap().clone()
-                } else {
-                    let parent_folder = actual_input.to_string_lossy().to_string();
-
-                    fn display_pathbuf(prefix: &str, pb: &Path) -> String {
-                        let full_path = pb.to_str().unwrap().to_owned();
-
-                        if full_path.starts_with(prefix) {
-                            full_path[prefix.len() + 1..].to_string()
-                        } else {
-                            full_path
-                        }
-                    }
-
-                    fn filename_len(path: &PathBuf) -> usize {
-                        path.file_name().expect("Must have filename").to_string_lossy().len()
-                    }
-
-                    //Sort exes by length, usually the one we want is the one with the shortest name
-                    exes.sort_by(|a,b |filename_len(a).cmp(&filename_len(b)));
-
-                    let def_exe_path = exes.first().unwrap().clone();
-                    let def_exe = display_pathbuf(&parent_folder, &def_exe_path);
-
-                    let question = format!(
-                        "Multiple exes where found: {}, which one do you want to use?",
-                        exes.iter()
-                            .map(|p| display_pathbuf(&parent_folder, p))
-                            .join(", ")
-                    );
-
-                    let mut exe_pb = None;
-                    while exe_pb.is_none() {
-                        let name = dialog::Input::new(&question)
-                            .title("Which executable?")
-                            .default(&def_exe)
-                            .show()
-                            .expect("Failed to show message")
-                            .unwrap();
-                        println!("{}",name);
-                        exe_pb = exes
-                            .iter()
-                            .find(|p| display_pathbuf(&parent_folder, p) == name);
-
-                        if exe_pb.is_none() {
-                            dialog::Message::new("Please select a valid executable")
-                                .show()
-                                .expect("Failed to show message")
-                        }
-                    }
-                    exe_pb.unwrap().clone()
-                }
-            };
-
-            let entry = DesktopFile::new(
-                executable
-                    .file_stem()
-                    .unwrap()
-                    .to_str()
-                    .unwrap()
-                    .to_string(),
-                Some(icon),
-                args.categories,
-                args.terminal,
-            );
-
-            let f_name = executable.file_name().expect("Executable must have a file name").to_string_lossy().to_string();
-            let id = format!("{}.to_appimage.com", f_name);
-            let desktop = format!("{}.desktop", id);
-            let app_desktop = File::create(actual_input.join(&desktop)).unwrap();
-            let whole_name = actual_input.file_name().expect("Input must have a file name");
-
-            desktop_entry::to_writer(app_desktop, &entry).unwrap();
-            std::fs::copy(&executable, actual_input.join("AppRun")).unwrap();
-
-   
-            // Make appstream
-            // usr/share/metainfo/myapp.appdata.xml
-            let summary = "TODO!TODO!".to_string();
-            let description = "TODO!TODO!".to_string();
-            const NAME_LIMIT: usize = 15;
-
-            let appstream = AppStream {
-                component: AppStreamComponent {
-                    ctype: if args.terminal {
-                        ComponentType::ConsoleApplication
-                    } else {
-                        ComponentType::DesktopApplication
-                    },
-                    id,
-                    metadata_license: License::CC0,
-                    project_license: License::locate(&actual_input).expect("Couldn't get the license"),
-                    name: whole_name.to_string_lossy()[0..std::cmp::min(whole_name.len(), NAME_LIMIT)].to_string(),
-                    summary,
-                    description: Description{p: description},
-                    launchable: Launchable {
-                        ctype: LaunchableType::DesktopId,
-                        name: desktop.clone()
-                    },
-                    url: Some(Url{ctype: appstream::UrlType::Homepage, data: "https://github.com/sheosi/to_appimage".to_string()}),
-                    screenshots: Screenshots{screenshot: vec![Screenshot{ctype: ScreenshotType::Default, image: "https://placehold.co/700x400.png".to_string()}]},
-                    provides: Provides{id: desktop.clone()},
-                    content_rating: ContentRating {t: "oars-1.0".to_string()}, // This is for a program that is not +18
-                },
-            };
-
-            appstream.write(&actual_input);
-
-            cmd::cached_app("appimagetool.appimage", &APPIMAGETOOL_LINKSET)
-                
-                .arg(&actual_input)
-                .arg("-n") // For the time being, ignore checking the appstram file, it appears the desktop file path is not correct, but don't know how to fix it
-                .run_outerr()
-                .unwrap();
-        }
+    #[test]
+    fn clean_subcommand() {
+        let cli = Cli::try_parse_from(["to_appimage", "clean"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Clean)));
     }
-
-    // TODO: Doesn't work properly
-    temp::clean_everything();
 }
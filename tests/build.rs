@@ -0,0 +1,400 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use tempfile::TempDir;
+use to_appimage::{build_appimage, build_batch, inspect, inspect_dir, licensing::License, read_manifest, Config, Error};
+
+/// Copies a fixture into a temporary directory unique to `test`, building
+/// modifies its input. The directory goes away when the guard is dropped,
+/// even if the test fails
+fn fixture(name: &str, test: &str) -> (TempDir, PathBuf) {
+    let src = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures").join(name);
+    let tmp = tempfile::Builder::new().prefix(&format!("to_appimage_it_{test}")).tempdir().unwrap();
+    let dst = tmp.path().join(name);
+    copy_dir(&src, &dst);
+
+    (tmp, dst)
+}
+
+fn copy_dir(src: &Path, dst: &Path) {
+    fs::create_dir_all(dst).unwrap();
+    for entry in fs::read_dir(src).unwrap().flatten() {
+        if entry.path().is_dir() {
+            copy_dir(&entry.path(), &dst.join(entry.file_name()));
+        } else {
+            fs::copy(entry.path(), dst.join(entry.file_name())).unwrap();
+        }
+    }
+}
+
+#[test]
+fn dry_run_stages_appdir() {
+    let (_tmp, dir) = fixture("simple", "dry_run");
+    let config = Config {
+        dry_run: true,
+        no_appstream_validate: true,
+        ..Config::new(dir.to_str().unwrap())
+    };
+
+    let output = build_appimage(&config).unwrap();
+
+    assert!(output.to_string_lossy().ends_with(".AppImage"));
+    assert!(dir.join("AppRun").exists());
+    assert!(dir.join("com.to_appimage.app.desktop").exists());
+    assert!(dir.join("usr/share/metainfo/com.to_appimage.app.appdata.xml").exists());
+}
+
+#[test]
+fn batch_attempts_every_entry() {
+    let (_tmp, dir) = fixture("simple", "batch");
+    let manifest = dir.parent().unwrap().join("manifest.txt");
+    fs::write(&manifest, format!("{}\n\n# Comments are skipped\n/this/path/does/not/exist\n", dir.display())).unwrap();
+
+    let base = Config {
+        dry_run: true,
+        no_appstream_validate: true,
+        ..Config::new("")
+    };
+    let results = build_batch(&base, read_manifest(&manifest).unwrap());
+
+    assert_eq!(results.len(), 2);
+    assert!(results[0].1.is_ok());
+    assert!(results[1].1.is_err());
+}
+
+#[test]
+fn single_directory_is_entered() {
+    let (_tmp, dir) = fixture("simple", "single_dir");
+    let config = Config {
+        dry_run: true,
+        no_appstream_validate: true,
+        ..Config::new(dir.parent().unwrap().to_str().unwrap())
+    };
+
+    build_appimage(&config).unwrap();
+
+    assert!(dir.join("AppRun").exists());
+    assert!(dir.join("com.to_appimage.app.desktop").exists());
+}
+
+#[test]
+fn shipped_apprun_is_kept() {
+    let (_tmp, dir) = fixture("apprun", "apprun");
+    let original = fs::read(dir.join("AppRun")).unwrap();
+    let config = Config {
+        dry_run: true,
+        no_appstream_validate: true,
+        ..Config::new(dir.to_str().unwrap())
+    };
+
+    build_appimage(&config).unwrap();
+
+    assert_eq!(fs::read(dir.join("AppRun")).unwrap(), original);
+}
+
+#[test]
+fn default_lang_is_tagged() {
+    let (_tmp, dir) = fixture("simple", "default_lang");
+    let config = Config {
+        dry_run: true,
+        no_appstream_validate: true,
+        name: Some("Calc".to_string()),
+        summary: Some("Adds numbers".to_string()),
+        default_lang: Some("es".to_string()),
+        ..Config::new(dir.to_str().unwrap())
+    };
+
+    build_appimage(&config).unwrap();
+
+    let desktop = fs::read_to_string(dir.join("com.to_appimage.app.desktop")).unwrap();
+    let metainfo = fs::read_to_string(dir.join("usr/share/metainfo/com.to_appimage.app.appdata.xml")).unwrap();
+
+    for line in ["Name=Calc\n", "Name[es]=Calc\n", "Comment=Adds numbers\n", "Comment[es]=Adds numbers\n"] {
+        assert!(desktop.contains(line), "missing {line:?} in {desktop}");
+    }
+    assert!(metainfo.contains("  <name>Calc</name>\n  <name xml:lang=\"es\">Calc</name>\n"));
+    assert!(metainfo.contains("  <summary>Adds numbers</summary>\n  <summary xml:lang=\"es\">Adds numbers</summary>\n"));
+}
+
+#[test]
+fn terminal_provides_binary() {
+    let (_tmp, dir) = fixture("simple", "terminal");
+    let config = Config {
+        dry_run: true,
+        no_appstream_validate: true,
+        terminal: true,
+        ..Config::new(dir.to_str().unwrap())
+    };
+
+    build_appimage(&config).unwrap();
+
+    let metainfo = fs::read_to_string(dir.join("usr/share/metainfo/com.to_appimage.app.appdata.xml")).unwrap();
+
+    assert!(metainfo.contains("<component type=\"console-application\">"));
+    assert!(metainfo.contains("  <provides>\n    <id>com.to_appimage.app.desktop</id>\n    <binary>app.sh</binary>\n  </provides>\n"));
+}
+
+#[test]
+fn inspect_appdir() {
+    let (_tmp, dir) = fixture("simple", "inspect");
+    let config = Config {
+        dry_run: true,
+        no_appstream_validate: true,
+        ..Config::new(dir.to_str().unwrap())
+    };
+    build_appimage(&config).unwrap();
+
+    let inspection = inspect(&dir).unwrap();
+
+    assert!(inspection.desktop_entry.unwrap().contains("Exec=./AppRun"));
+    assert!(inspection.metainfo.unwrap().contains("<id>com.to_appimage.app</id>"));
+    assert_eq!(inspection.license, Some(License::Mit));
+}
+
+#[test]
+fn description_from_file() {
+    let (_tmp, dir) = fixture("simple", "description_file");
+    let config = Config {
+        dry_run: true,
+        no_appstream_validate: true,
+        description: Some("Ignored".to_string()),
+        description_file: Some(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/description.md")),
+        ..Config::new(dir.to_str().unwrap())
+    };
+
+    build_appimage(&config).unwrap();
+
+    let metainfo = fs::read_to_string(dir.join("usr/share/metainfo/com.to_appimage.app.appdata.xml")).unwrap();
+
+    assert!(metainfo.contains(concat!(
+        "  <description>\n",
+        "    <p>Calc is a small calculator for the terminal.</p>\n",
+        "    <p>It keeps a history of every result.</p>\n",
+        "  </description>\n"
+    )));
+}
+
+#[test]
+fn missing_description_file() {
+    let (_tmp, dir) = fixture("simple", "missing_description_file");
+    let config = Config {
+        dry_run: true,
+        no_appstream_validate: true,
+        description_file: Some(dir.join("DESCRIPTION")),
+        ..Config::new(dir.to_str().unwrap())
+    };
+
+    let error = build_appimage(&config).unwrap_err();
+
+    assert!(error.to_string().contains("DESCRIPTION"));
+    assert_eq!(error.exit_code(), 2);
+}
+
+#[test]
+fn inspect_dir_writes_nothing() {
+    let (_tmp, dir) = fixture("simple", "inspect_dir");
+    let before = fs::read_dir(&dir).unwrap().count();
+
+    let detection = serde_json::to_value(inspect_dir(&dir).unwrap()).unwrap();
+    let after = fs::read_dir(&dir).unwrap().count();
+
+    assert_eq!(before, after);
+    assert!(detection["executable"].as_str().unwrap().ends_with("app.sh"));
+    assert_eq!(detection["license"], "MIT");
+    assert_eq!(detection["icon"]["source"], "app-icon");
+    assert_eq!(detection["categories"][0], "Utility");
+}
+
+#[test]
+fn export_metadata_skips_appimagetool() {
+    let (_tmp, dir) = fixture("simple", "export_metadata");
+    let export = dir.parent().unwrap().join("metadata");
+    let config = Config {
+        export_metadata: Some(export.clone()),
+        no_appstream_validate: true,
+        ..Config::new(dir.to_str().unwrap())
+    };
+
+    let output = build_appimage(&config).unwrap();
+
+    assert_eq!(output, export);
+    assert!(export.join("com.to_appimage.app.desktop").exists());
+    assert!(export.join("com.to_appimage.app.appdata.xml").exists());
+    assert!(export.join("AppIcon.svg").exists());
+    // appimagetool would have left an AppImage in the working directory
+    assert!(!Path::new(&format!("app-{}.AppImage", std::env::consts::ARCH)).exists());
+}
+
+#[test]
+fn appdir_out_has_everything_but_the_appimage() {
+    let (_tmp, dir) = fixture("simple", "appdir_out");
+    let out = dir.parent().unwrap().join("AppDir");
+    let config = Config {
+        appdir_out: Some(out.clone()),
+        no_appstream_validate: true,
+        ..Config::new(dir.to_str().unwrap())
+    };
+
+    let output = build_appimage(&config).unwrap();
+
+    assert_eq!(output, out);
+    assert!(out.join("AppRun").is_file());
+    assert!(out.join("com.to_appimage.app.desktop").exists());
+    assert!(out.join("usr/share/applications/com.to_appimage.app.desktop").exists());
+    assert!(out.join("usr/share/metainfo/com.to_appimage.app.appdata.xml").exists());
+    assert!(out.join("AppIcon.svg").exists());
+    assert!(!Path::new(&format!("app-{}.AppImage", std::env::consts::ARCH)).exists());
+}
+
+#[test]
+fn reproducible_stagings_match() {
+    let stage = |test| {
+        let (_tmp, dir) = fixture("simple", test);
+        let config = Config {
+            dry_run: true,
+            reproducible: true,
+            no_appstream_validate: true,
+            ..Config::new(dir.to_str().unwrap())
+        };
+        build_appimage(&config).unwrap();
+
+        let read = |file: &str| fs::read(dir.join(file)).unwrap();
+        (read("com.to_appimage.app.desktop"), read("usr/share/metainfo/com.to_appimage.app.appdata.xml"))
+    };
+
+    assert_eq!(stage("reproducible_first"), stage("reproducible_second"));
+}
+
+#[test]
+fn metadata_license_is_written() {
+    let (_tmp, dir) = fixture("simple", "metadata_license");
+    let config = Config {
+        dry_run: true,
+        no_appstream_validate: true,
+        metadata_license: License::metadata("FSFAP").unwrap(),
+        ..Config::new(dir.to_str().unwrap())
+    };
+
+    build_appimage(&config).unwrap();
+    let metainfo = fs::read_to_string(dir.join("usr/share/metainfo/com.to_appimage.app.appdata.xml")).unwrap();
+
+    assert!(metainfo.contains("<metadata_license>FSFAP</metadata_license>"));
+}
+
+#[test]
+fn json_report_of_dry_run() {
+    let (_tmp, dir) = fixture("simple", "json_report");
+    let report = dir.parent().unwrap().join("report.json");
+    let config = Config {
+        dry_run: true,
+        no_appstream_validate: true,
+        json_report: Some(report.clone()),
+        ..Config::new(dir.to_str().unwrap())
+    };
+
+    build_appimage(&config).unwrap();
+    let report: serde_json::Value = serde_json::from_str(&fs::read_to_string(&report).unwrap()).unwrap();
+
+    assert!(report["output"].as_str().unwrap().ends_with(".AppImage"));
+    assert_eq!(report["id"], "com.to_appimage.app");
+    assert_eq!(report["name"], "app");
+    assert_eq!(report["license"], "MIT");
+    assert!(report["executable"].as_str().unwrap().ends_with("app.sh"));
+    assert_eq!(report["icon"]["source"], "app-icon");
+    assert!(report.get("error").is_none());
+    assert!(report.get("sha256").is_none());
+}
+
+#[test]
+fn app_id_names_the_desktop_file() {
+    let (_tmp, dir) = fixture("simple", "app_id");
+    let config = Config {
+        dry_run: true,
+        no_appstream_validate: true,
+        app_id: Some("com.example.Foo".to_string()),
+        ..Config::new(dir.to_str().unwrap())
+    };
+
+    build_appimage(&config).unwrap();
+    let metainfo = fs::read_to_string(dir.join("usr/share/metainfo/com.example.Foo.appdata.xml")).unwrap();
+    let root_desktop = dir.join("com.example.Foo.desktop").exists();
+    let installed_desktop = dir.join("usr/share/applications/com.example.Foo.desktop").exists();
+
+    assert!(root_desktop && installed_desktop);
+    assert!(metainfo.contains("<id>com.example.Foo</id>"));
+    assert!(metainfo.contains("<launchable type=\"desktop-id\">com.example.Foo.desktop</launchable>"));
+    assert!(metainfo.contains("<id>com.example.Foo.desktop</id>"));
+}
+
+#[test]
+fn flatpak_command_is_the_apprun_target() {
+    let (_tmp, dir) = fixture("flatpak", "flatpak");
+    let out = dir.parent().unwrap().join("AppDir");
+    let dry_run = Config {
+        dry_run: true,
+        ..Config::new(dir.to_str().unwrap())
+    };
+    let config = Config {
+        appdir_out: Some(out.clone()),
+        ..Config::new(dir.to_str().unwrap())
+    };
+
+    let output = build_appimage(&dry_run).unwrap();
+    build_appimage(&config).unwrap();
+    let apprun = fs::read_to_string(out.join("AppRun")).unwrap();
+    let desktop = fs::read_to_string(out.join("org.example.Hello.desktop")).unwrap();
+    let icon = out.join("org.example.Hello.svg").exists();
+    let metainfo = out.join("usr/share/metainfo/org.example.Hello.metainfo.xml").exists();
+    // The export is the user's, the AppDir is made in a copy
+    let export_untouched = !dir.join("AppRun").exists() && fs::symlink_metadata(dir.join("usr")).is_err();
+
+    assert!(output.to_string_lossy().starts_with("Hello_Flatpak-"));
+    assert!(apprun.contains("exec \"$HERE/files/bin/hello\""));
+    assert!(desktop.contains("Name=Hello Flatpak"));
+    assert!(icon && metainfo);
+    assert!(export_untouched);
+}
+
+#[test]
+fn shipped_appdata_is_merged() {
+    let (_tmp, dir) = fixture("appdata", "appdata");
+    let config = Config {
+        dry_run: true,
+        no_appstream_validate: true,
+        keywords: vec!["notes".to_string()],
+        ..Config::new(dir.to_str().unwrap())
+    };
+
+    build_appimage(&config).unwrap();
+
+    let desktop = fs::read_to_string(dir.join("com.to_appimage.app.desktop")).unwrap();
+    let metainfo = fs::read_to_string(dir.join("usr/share/metainfo/com.to_appimage.app.appdata.xml")).unwrap();
+    // It's the user's folder, nothing of theirs is deleted
+    let shipped_kept = dir.join("usr/share/metainfo/org.example.Notes.appdata.xml").exists();
+
+    assert!(shipped_kept);
+    assert!(metainfo.contains("<summary>Jot things down quickly</summary>"), "{metainfo}");
+    assert!(metainfo.contains("<p>Keeps your notes in plain text files.</p>"));
+    assert!(metainfo.contains("<name xml:lang=\"es\">Notas</name>"));
+    assert!(metainfo.contains("<keyword>notes</keyword>"));
+    for line in ["Name=Notes\n", "Comment=Jot things down quickly\n", "Categories=Office;\n"] {
+        assert!(desktop.contains(line), "missing {line:?} in {desktop}");
+    }
+}
+
+#[test]
+fn offline_refuses_remote_targets() {
+    let url = "http://127.0.0.1:9/app.tar.gz";
+    let config = Config {
+        offline: true,
+        no_cache: true,
+        ..Config::new(url)
+    };
+
+    let error = build_appimage(&config).unwrap_err();
+
+    assert!(matches!(&error, Error::Offline(what) if what == url), "{error}");
+    assert!(error.to_string().contains("--offline"));
+}
@@ -0,0 +1,16 @@
+use std::process::Command;
+
+#[test]
+fn nonexistent_input() {
+    let out = Command::new(env!("CARGO_BIN_EXE_to_appimage"))
+        .arg("/this/path/does/not/exist")
+        .output()
+        .unwrap();
+
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(!out.status.success());
+    // 101 is what Rust uses for panics
+    assert_ne!(out.status.code(), Some(101));
+    assert!(stderr.starts_with("Error: "));
+    assert!(!stderr.contains("panicked"));
+}
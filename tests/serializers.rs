@@ -0,0 +1,132 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use to_appimage::{
+    appstream::{
+        AppStream, AppStreamComponent, ComponentType, ContentRating, Custom, Description, Keywords, Launchable, LocalizedString, Provides,
+        RatingProfile, Releases, Screenshots, Shipped, SupportsControl,
+    },
+    desktop_entry::{get_value, split_list, to_string, to_writer, unescape},
+    licensing::License,
+};
+
+#[derive(Serialize)]
+struct Entry {
+    #[serde(rename = "Desktop Entry")]
+    main: Main,
+    #[serde(rename = "Desktop Action new-window")]
+    action: Action,
+}
+
+#[derive(Serialize)]
+struct Main {
+    #[serde(rename = "Name")]
+    name: String,
+    #[serde(rename = "Comment")]
+    comment: BTreeMap<String, String>,
+    #[serde(rename = "Categories")]
+    categories: Vec<String>,
+    #[serde(rename = "Terminal")]
+    terminal: bool,
+    #[serde(rename = "TryExec")]
+    try_exec: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Action {
+    #[serde(rename = "Name")]
+    name: String,
+    #[serde(rename = "Exec")]
+    exec: String,
+}
+
+fn entry() -> Entry {
+    Entry {
+        main: Main {
+            name: "Notes\\Ideas".to_string(),
+            comment: BTreeMap::from([("es".to_string(), "Primera línea\nSegunda".to_string())]),
+            categories: vec!["Office".to_string(), "Text;Editor".to_string()],
+            terminal: false,
+            try_exec: None,
+        },
+        action: Action { name: "New window".to_string(), exec: "notes --new %U".to_string() },
+    }
+}
+
+#[test]
+fn desktop_entry_layout() {
+    assert_eq!(
+        to_string(&entry()).unwrap(),
+        "[Desktop Entry]
+Name=Notes\\\\Ideas
+Comment[es]=Primera línea\\nSegunda
+Categories=Office;Text\\;Editor;
+Terminal=false
+
+[Desktop Action new-window]
+Name=New window
+Exec=notes --new %U
+"
+    );
+}
+
+#[test]
+fn desktop_entry_round_trips() {
+    let mut written = Vec::new();
+    to_writer(&mut written, &entry()).unwrap();
+    let contents = String::from_utf8(written).unwrap();
+
+    assert_eq!(unescape(get_value(&contents, "Desktop Entry", "Name").unwrap()), "Notes\\Ideas");
+    assert_eq!(split_list(get_value(&contents, "Desktop Entry", "Categories").unwrap()), ["Office", "Text;Editor"]);
+    assert_eq!(get_value(&contents, "Desktop Entry", "Terminal"), Some("false"));
+    assert_eq!(get_value(&contents, "Desktop Entry", "TryExec"), None);
+    assert_eq!(get_value(&contents, "Desktop Action new-window", "Exec"), Some("notes --new %U"));
+    assert!(contents.contains("Comment[es]=Primera línea\\nSegunda\n"));
+    assert_eq!(unescape("Primera línea\\nSegunda"), "Primera línea\nSegunda");
+}
+
+#[test]
+fn desktop_entry_rejects_deeper_levels() {
+    let too_deep = BTreeMap::from([("Desktop Entry", BTreeMap::from([("Name", BTreeMap::from([("es", BTreeMap::from([("x", "y")]))]))]))]);
+    let bad_key = BTreeMap::from([("Desktop Entry", BTreeMap::from([("Bad_Key", "value")]))]);
+
+    assert!(to_string(&too_deep).is_err());
+    assert!(to_string(&bad_key).is_err());
+}
+
+#[test]
+fn appstream_round_trips() {
+    let id = "org.example.Notes";
+    let appstream = AppStream {
+        component: AppStreamComponent {
+            ctype: ComponentType::DesktopApplication,
+            id: id.to_string(),
+            metadata_license: License::CC0,
+            project_license: License::Mit,
+            name: LocalizedString::new("Notes".to_string()).with_translations([("es".to_string(), "Notas".to_string())]),
+            summary: LocalizedString::new("Jot things down".to_string()),
+            description: Description::parse("Keeps your notes.\n\n- Fast\n- Plain text"),
+            launchable: Launchable::desktop(id),
+            content_rating: ContentRating::from_profile(RatingProfile::None),
+            url: None,
+            keywords: Keywords::default(),
+            project_group: None,
+            update_contact: None,
+            screenshots: Screenshots { screenshot: vec![] },
+            provides: Provides::desktop(id),
+            recommends: SupportsControl::default(),
+            releases: Releases::current(Some("1.0")),
+            custom: Custom::default(),
+        },
+    };
+
+    let xml = appstream.to_xml().unwrap();
+    let shipped = Shipped::parse(&xml).unwrap();
+
+    assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<component type=\"desktop-application\">\n"));
+    let name = shipped.name().unwrap();
+    assert_eq!(name.default, "Notes");
+    assert_eq!(name.translations, BTreeMap::from([("es".to_string(), "Notas".to_string())]));
+    assert_eq!(shipped.summary().unwrap().default, "Jot things down");
+    assert_eq!(shipped.description.unwrap().blocks.len(), 2);
+}